@@ -1,10 +1,24 @@
-use crate::swap::SwapDirection;
+use crate::cmd::{ChartKind, Perturbation};
 use clap::{Parser, Subcommand};
+use solana_pubkey::Pubkey;
+use solana_sdk::hash::Hash;
+use solfi_sim::ReferenceKind;
+use solfi_sim::quoter::FitKind;
+use solfi_sim::swap::SwapDirection;
+use std::path::PathBuf;
 
 #[derive(Debug, Subcommand)]
 pub enum Command {
     /// Fetch the solfi wsol/usdc pool accounts and related data
-    FetchAccounts,
+    FetchAccounts {
+        /// Also keep a copy of the snapshot under <data dir>/snapshots/<slot>
+        #[arg(long)]
+        archive: bool,
+
+        /// JSON file of other venues whose accounts to fetch into their snapshot directories
+        #[arg(long)]
+        venues: Option<PathBuf>,
+    },
 
     /// Print slot cutoff and other metadata from fetched solfi pool data
     Cutoffs,
@@ -32,23 +46,399 @@ pub enum Command {
         /// Don't print simulation errors
         #[arg(long)]
         ignore_errors: bool,
+
+        /// Compute unit limit to request (default: the runtime's)
+        #[arg(long)]
+        compute_unit_limit: Option<u32>,
+
+        /// Priority fee in micro-lamports per compute unit
+        #[arg(long)]
+        compute_unit_price: Option<u64>,
+
+        /// Search each market for the smallest compute unit limit the swap succeeds with and use it
+        #[arg(long)]
+        min_compute_units: bool,
+
+        /// JSON file describing other venues to quote alongside SolFi
+        #[arg(long)]
+        venues: Option<PathBuf>,
+
+        /// Also quote a textbook pool holding each SolFi market's vault balances (repeatable)
+        #[arg(long = "reference", value_name = "MODEL")]
+        references: Vec<ReferenceKind>,
+
+        /// Fee of the constant-product and stableswap reference models
+        #[arg(long, default_value_t = 0.0)]
+        reference_fee_bps: f64,
+
+        /// Amplification coefficient of the stableswap reference model
+        #[arg(long, default_value_t = 100.0)]
+        amplification: f64,
+
+        /// Spread of the fixed-spread reference model around the market's mid price
+        #[arg(long, default_value_t = 0.0)]
+        spread_bps: f64,
+
+        /// Print every column (error kind, costs, compute usage, venue) under a header row.
+        /// Implied by the compute budget options, --venues and --reference
+        #[arg(long)]
+        wide: bool,
+    },
+
+    /// Build a synthetic L2 order book per market from simulated curves
+    Book {
+        /// Number of price levels on each side
+        #[arg(short, long, default_value_t = 10)]
+        levels: usize,
+
+        /// SOL size of each level
+        #[arg(short, long, default_value_t = 10.0)]
+        step: f64,
+
+        /// Price tick used to aggregate levels across markets
+        #[arg(short, long, default_value_t = 0.01)]
+        tick: f64,
+
+        /// Print the aggregated book as JSON instead of tables
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Search for profitable cross-market round trips within the current snapshot
+    Arb {
+        /// Smallest USDC amount to try
+        #[arg(long, default_value_t = 100.0)]
+        min_usdc: f64,
+
+        /// Largest USDC amount to try
+        #[arg(long, default_value_t = 100_000.0)]
+        max_usdc: f64,
+
+        /// Number of sizes to try, spaced geometrically between min and max
+        #[arg(long, default_value_t = 12)]
+        steps: usize,
+    },
+
+    /// Check that every market's curve behaves: output grows with size, marginal price worsens,
+    /// round trips lose money and both directions agree
+    CheckInvariants {
+        /// Smallest SOL size to check (USDC sizes are matched at the best bid)
+        #[arg(long, default_value_t = 0.01)]
+        min_sol: f64,
+
+        /// Largest SOL size to check
+        #[arg(long, default_value_t = 1_000.0)]
+        max_sol: f64,
+
+        /// Number of sizes to check, spaced geometrically between min and max
+        #[arg(long, default_value_t = 12)]
+        steps: usize,
+
+        /// Widest spread between buying and selling allowed at the smallest size
+        #[arg(long, default_value_t = 100.0)]
+        max_spread_bps: f64,
+
+        /// How much a marginal price may improve with size before it counts as broken
+        #[arg(long, default_value_t = 0.01)]
+        tolerance_bps: f64,
+
+        /// Print the violations as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Mark quotes from archived snapshots against a reference mid price file
+    Markout {
+        /// CSV of `timestamp,price` reference mids (unix seconds or milliseconds)
+        reference: PathBuf,
+
+        /// Snapshot directories to mark (default: everything under <data dir>/snapshots)
+        #[arg(long, num_args = 1..)]
+        snapshots: Vec<PathBuf>,
+
+        /// Comma-separated SOL sizes to quote
+        #[arg(short, long, value_delimiter = ',', default_value = "1,10,100")]
+        sizes: Vec<f64>,
+
+        /// Comma-separated markout horizons in slots
+        #[arg(short = 'n', long, value_delimiter = ',', default_value = "0,10,150")]
+        horizons: Vec<u64>,
+
+        /// Also mark snapshots timed by the fetching machine's clock, which the RPC had no block
+        /// time for. Their markouts are only as aligned as that clock
+        #[arg(long)]
+        local_clock: bool,
+    },
+
+    /// Fit a compact curve model to each market for fast approximate quoting
+    Fit {
+        /// Number of sizes to sample per market and direction
+        #[arg(short, long, default_value_t = 16)]
+        points: usize,
+
+        /// Model to fit
+        #[arg(short, long, default_value_t = FitKind::PiecewiseLinear, value_enum)]
+        kind: FitKind,
+
+        /// Polynomial degree (only used with --kind polynomial)
+        #[arg(long, default_value_t = 3)]
+        degree: usize,
+
+        /// Smallest SOL size to sample (USDC sizes are matched at the best bid)
+        #[arg(long, default_value_t = 0.1)]
+        min_sol: f64,
+
+        /// Largest SOL size to sample
+        #[arg(long, default_value_t = 1000.0)]
+        max_sol: f64,
+
+        /// Where to save the fitted model (default: <data dir>/curve_model.json)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Draw charts of the simulated curves, spreads or archived quotes as SVG or PNG
+    Plot {
+        /// What to draw
+        #[arg(value_enum)]
+        chart: ChartKind,
+
+        /// The direction of the swap (curves, price and history charts)
+        #[arg(short, long, default_value_t = SwapDirection::SolToUsdc)]
+        direction: SwapDirection,
+
+        /// Smallest SOL size to plot (USDC sizes are matched at the best bid)
+        #[arg(long, default_value_t = 0.1)]
+        min_sol: f64,
+
+        /// Largest SOL size to plot
+        #[arg(long, default_value_t = 1_000.0)]
+        max_sol: f64,
+
+        /// Number of sizes to plot per market
+        #[arg(long, default_value_t = 40)]
+        points: usize,
+
+        /// SOL size to quote in each snapshot (history chart)
+        #[arg(long, default_value_t = 10.0)]
+        size: f64,
+
+        /// File to write, SVG if it ends in .svg and PNG otherwise (default: <chart>_<slot>.png)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Quote all markets from a fitted curve model without running the SVM
+    ApproxQuote {
+        /// Model saved by `fit` (default: <data dir>/curve_model.json)
+        #[arg(short, long)]
+        model: Option<PathBuf>,
+
+        /// The direction of the swap
+        #[arg(short, long, default_value_t = SwapDirection::SolToUsdc)]
+        direction: SwapDirection,
+
+        /// Amount of SOL or USDC to swap. Input mint depends on --direction
+        #[arg(short, long)]
+        amount: f64,
+    },
+
+    /// Trace which account bytes the SolFi program reads and writes during a swap
+    Trace {
+        /// Market to swap on
+        #[arg(short, long)]
+        market: Pubkey,
+
+        /// The direction of the swap
+        #[arg(short, long, default_value_t = SwapDirection::SolToUsdc)]
+        direction: SwapDirection,
+
+        /// Amount of SOL or USDC to swap. Input mint depends on --direction
+        #[arg(short, long, default_value_t = 10.0)]
+        amount: f64,
+
+        /// Print the report as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Perturb the market account one field at a time and rank fields by how much a swap moves
+    Sensitivity {
+        /// Market whose account is perturbed
+        #[arg(short, long)]
+        market: Pubkey,
+
+        /// The direction of the swap
+        #[arg(short, long, default_value_t = SwapDirection::SolToUsdc)]
+        direction: SwapDirection,
+
+        /// Amount of SOL or USDC to swap. Input mint depends on --direction
+        #[arg(short, long, default_value_t = 10.0)]
+        amount: f64,
+
+        /// How each field is perturbed
+        #[arg(long, default_value_t = Perturbation::Scale)]
+        mode: Perturbation,
+
+        /// Multiplier used by --mode scale
+        #[arg(long, default_value_t = 1.01)]
+        factor: f64,
+
+        /// Field width in bytes (1, 2, 4 or 8). Fields are aligned to their width
+        #[arg(long, default_value_t = 8)]
+        width: usize,
+
+        /// First byte of the range to perturb
+        #[arg(long, default_value_t = 0)]
+        start: usize,
+
+        /// End of the range to perturb (exclusive, default: end of the account)
+        #[arg(long)]
+        end: Option<usize>,
+
+        /// Number of fields to print
+        #[arg(long, default_value_t = 20)]
+        top: usize,
+    },
+
+    /// Send controlled values in the undocumented swap instruction bytes and other discriminators
+    IxExplore {
+        /// Market to swap on
+        #[arg(short, long)]
+        market: Pubkey,
+
+        /// The direction of the swap
+        #[arg(short, long, default_value_t = SwapDirection::SolToUsdc)]
+        direction: SwapDirection,
+
+        /// Amount of SOL or USDC to swap. Input mint depends on --direction
+        #[arg(short, long, default_value_t = 10.0)]
+        amount: f64,
+    },
+
+    /// Decode the SolFi instructions in a transaction saved from getTransaction
+    DecodeTx {
+        /// JSON file with the getTransaction response (any encoding)
+        file: PathBuf,
+
+        /// Print the decoded instructions as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Replay saved SolFi transactions against a snapshot and compare outputs with the chain
+    Validate {
+        /// JSON files with getTransaction responses
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+
+        /// Snapshot taken shortly before the transactions (default: the data directory)
+        #[arg(long)]
+        snapshot: Option<PathBuf>,
+    },
+
+    /// Build an unsigned swap transaction for a wallet and check it against the snapshot
+    BuildTx {
+        /// Wallet that will sign and pay for the transaction
+        #[arg(short, long)]
+        user: Pubkey,
+
+        /// Market to swap on
+        #[arg(short, long)]
+        market: Pubkey,
+
+        /// The direction of the swap
+        #[arg(short, long, default_value_t = SwapDirection::SolToUsdc)]
+        direction: SwapDirection,
+
+        /// Amount of SOL or USDC to swap. Input mint depends on --direction
+        #[arg(short, long)]
+        amount: f64,
+
+        /// Fail the swap if it pays out more than this much less than the simulated output. Uses
+        /// the undocumented swap bytes 9..17, only after checking on the snapshot that they act
+        /// as a minimum output
+        #[arg(long)]
+        slippage_bps: Option<u32>,
+
+        /// Compute unit limit for the transaction
+        #[arg(long, default_value_t = 200_000)]
+        compute_unit_limit: u32,
+
+        /// Priority fee in micro-lamports per compute unit
+        #[arg(long, default_value_t = 0)]
+        compute_unit_price: u64,
+
+        /// Recent blockhash to use (default: fetched from the RPC endpoints)
+        #[arg(long)]
+        blockhash: Option<Hash>,
     },
 
     /// Start simulation service
     Service {
-        /// Port to run the JSON-RPC server on
-        #[arg(short, long, default_value = "8080")]
+        /// Port to run the JSON-RPC server on (default: service.port from the config, 8080)
+        #[arg(short, long)]
+        port: Option<u16>,
+
+        /// Fetch interval in milliseconds (default: service.fetch_interval_ms from the config, 400)
+        #[arg(short = 'i', long)]
+        fetch_interval_ms: Option<u64>,
+
+        /// Follow the pool accounts with accountSubscribe, polling only while the WebSocket is down
+        #[arg(long)]
+        subscribe: bool,
+
+        /// PubSub WebSocket endpoint (default: service.ws_url from the config, or the RPC URL with
+        /// a ws scheme)
+        #[arg(long)]
+        ws_url: Option<String>,
+    },
+
+    /// Show live quotes per market in the terminal, refreshed from the fetch loop
+    Dashboard {
+        /// Fetch interval in milliseconds (default: service.fetch_interval_ms from the config, 400)
+        #[arg(short = 'i', long)]
+        fetch_interval_ms: Option<u64>,
+
+        /// Follow the pool accounts with accountSubscribe, polling only while the WebSocket is down
+        #[arg(long)]
+        subscribe: bool,
+
+        /// PubSub WebSocket endpoint (default: service.ws_url from the config, or the RPC URL with
+        /// a ws scheme)
+        #[arg(long)]
+        ws_url: Option<String>,
+    },
+
+    /// Serve the snapshot in the data directory as a mock Solana RPC node, for offline testing
+    #[cfg(feature = "mock-rpc")]
+    #[command(hide = true)]
+    MockRpc {
+        /// Port to listen on
+        #[arg(short, long, default_value = "8899")]
         port: u16,
 
-        /// Fetch interval in milliseconds
-        #[arg(short = 'i', long, default_value = "400")]
-        fetch_interval_ms: u64,
+        /// JSON script of slot progression, delays and failures
+        #[arg(long)]
+        script: Option<PathBuf>,
     },
 }
 
 #[derive(Debug, Parser)]
 #[clap(name = "app", version)]
 pub struct App {
+    /// Config file (default: solfi-sim.toml in the working directory, if there is one)
+    #[arg(long, global = true, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
+    /// Data directory, overriding the config and SOLFI_SIM_DATA_DIR
+    #[arg(long, global = true, value_name = "DIR")]
+    pub data_dir: Option<PathBuf>,
+
+    /// RPC endpoint, overriding the config and RPC_URL. Repeat to give several
+    #[arg(long = "rpc-url", global = true, value_name = "URL")]
+    pub rpc_urls: Vec<String>,
+
     #[clap(subcommand)]
     pub command: Command,
 }
@@ -0,0 +1,120 @@
+use crate::cmd::display_cutoffs;
+use crate::config;
+use serde::Serialize;
+use solana_sdk::native_token::lamports_to_sol;
+use solfi_sim::swap::SwapDirection;
+use solfi_sim::{Simulator, SwapResult};
+use std::collections::HashMap;
+use utoipa::ToSchema;
+
+/// Base fee for a single-signature transaction. Each leg of the arb is its own transaction.
+const SIGNATURE_FEE_LAMPORTS: u64 = 5_000;
+
+#[derive(Serialize, Debug, Clone, ToSchema)]
+pub struct ArbOpportunity {
+    pub buy_market: String,
+    pub sell_market: String,
+    pub usdc_in: f64,
+    pub sol_amount: f64,
+    pub usdc_out: f64,
+    pub fees_usdc: f64,
+    pub profit_usdc: f64,
+    pub profit_bps: f64,
+}
+
+/// Returns `steps` sizes spaced geometrically between `min` and `max` inclusive.
+pub fn geometric_sizes(min: f64, max: f64, steps: usize) -> Vec<f64> {
+    if steps <= 1 || max <= min {
+        return vec![min];
+    }
+    let ratio = (max / min).powf(1.0 / (steps - 1) as f64);
+    (0..steps).map(|i| min * ratio.powi(i as i32)).collect()
+}
+
+/// Searches `usdc_sizes` for round trips that buy SOL on one market and sell it on another, all
+/// against the snapshot loaded in `simulator`. Returns the most profitable size for each market
+/// pair that makes money after network fees, best first. Every batch of quotes it simulates is
+/// passed to `on_quotes`.
+pub fn find_arbs(
+    simulator: &Simulator,
+    usdc_sizes: &[f64],
+    mut on_quotes: impl FnMut(&[SwapResult]),
+) -> eyre::Result<Vec<ArbOpportunity>> {
+    let mut best: HashMap<(String, String), ArbOpportunity> = HashMap::new();
+
+    for &usdc_in in usdc_sizes {
+        let buys = simulator.quote_all(SwapDirection::UsdcToSol, usdc_in)?;
+        on_quotes(&buys);
+        for buy in buys {
+            let Some(sol_amount) = buy.out_amount.filter(|sol| *sol > 0.0) else {
+                continue;
+            };
+            let sells = simulator.quote_all(SwapDirection::SolToUsdc, sol_amount)?;
+            on_quotes(&sells);
+            for sell in sells {
+                let Some(usdc_out) = sell.out_amount else {
+                    continue;
+                };
+                if sell.market == buy.market {
+                    continue;
+                }
+
+                let sell_price = usdc_out / sol_amount;
+                let fees_usdc = lamports_to_sol(2 * SIGNATURE_FEE_LAMPORTS) * sell_price;
+                let profit_usdc = usdc_out - usdc_in - fees_usdc;
+                if profit_usdc <= 0.0 {
+                    continue;
+                }
+
+                let opportunity = ArbOpportunity {
+                    buy_market: buy.market.clone(),
+                    sell_market: sell.market.clone(),
+                    usdc_in,
+                    sol_amount,
+                    usdc_out,
+                    fees_usdc,
+                    profit_usdc,
+                    profit_bps: profit_usdc / usdc_in * 10_000.0,
+                };
+                let key = (opportunity.buy_market.clone(), opportunity.sell_market.clone());
+                match best.get(&key) {
+                    Some(existing) if existing.profit_usdc >= profit_usdc => {}
+                    _ => {
+                        best.insert(key, opportunity);
+                    }
+                }
+            }
+        }
+    }
+
+    let mut opportunities: Vec<_> = best.into_values().collect();
+    opportunities.sort_by(|a, b| b.profit_usdc.total_cmp(&a.profit_usdc));
+    Ok(opportunities)
+}
+
+/// Prints every profitable cross-market round trip in the current snapshot.
+pub fn detect_arbs(min_usdc: f64, max_usdc: f64, steps: usize) -> eyre::Result<()> {
+    display_cutoffs();
+    let sizes = geometric_sizes(min_usdc, max_usdc, steps);
+    println!(
+        "\nSearching {} sizes between {min_usdc:.2} and {max_usdc:.2} USDC for cross-market arbs...\n",
+        sizes.len()
+    );
+
+    let simulator = Simulator::from_config(config(), None)?;
+    let opportunities = find_arbs(&simulator, &sizes, |_| {})?;
+    if opportunities.is_empty() {
+        println!("No profitable cross-market round trip found.");
+        return Ok(());
+    }
+
+    for arb in opportunities {
+        println!("--- Buy on {} / Sell on {} ---", arb.buy_market, arb.sell_market);
+        println!("  Best size:   {:<12.2} USDC ({:.6} SOL)", arb.usdc_in, arb.sol_amount);
+        println!("  USDC out:    {:<12.6}", arb.usdc_out);
+        println!("  Fees:        {:<12.6} USDC", arb.fees_usdc);
+        println!("  Profit:      {:<12.6} USDC ({:.2} bps)\n", arb.profit_usdc, arb.profit_bps);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,170 @@
+use crate::cmd::{display_cutoffs, simulate};
+use crate::config;
+use serde::Serialize;
+use solfi_sim::swap::SwapDirection;
+use solfi_sim::types::FetchMetadata;
+use std::collections::{BTreeMap, HashMap};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const SYMBOL: &str = "SOL/USDC";
+
+#[derive(Serialize, Debug, Clone, Copy)]
+struct PriceLevel {
+    /// Marginal price of SOL in USDC across this level
+    price: f64,
+    /// SOL size available at this level
+    size: f64,
+}
+
+#[derive(Serialize, Debug)]
+struct MarketBook {
+    market: String,
+    bids: Vec<PriceLevel>,
+    asks: Vec<PriceLevel>,
+}
+
+/// Aggregated book in the same layout as the CEX depth snapshots: `[price, size]` pairs with bids
+/// sorted best (highest) first and asks sorted best (lowest) first.
+#[derive(Serialize, Debug)]
+struct AggregatedBook {
+    symbol: String,
+    slot: Option<u64>,
+    timestamp: u64,
+    bids: Vec<[f64; 2]>,
+    asks: Vec<[f64; 2]>,
+}
+
+/// Cumulative (in, out) amounts for a market, one entry per sampled size.
+type Curve = Vec<(f64, f64)>;
+
+fn sample_curves(direction: SwapDirection, sizes: &[f64]) -> eyre::Result<HashMap<String, Curve>> {
+    let mut curves: HashMap<String, Curve> = HashMap::new();
+    for size in sizes {
+        for result in simulate(direction, Some(*size), None, true, false)? {
+            if let Some(out) = result.out_amount {
+                curves.entry(result.market).or_default().push((result.in_amount, out));
+            }
+        }
+    }
+    Ok(curves)
+}
+
+/// Turns a cumulative curve into discrete levels. `price_of` maps the (in, out) deltas of a level
+/// to its (price, SOL size).
+fn curve_to_levels(
+    curve: &[(f64, f64)],
+    price_of: impl Fn(f64, f64) -> (f64, f64),
+) -> Vec<PriceLevel> {
+    let mut levels = Vec::new();
+    let mut prev = (0.0, 0.0);
+    for &(amount_in, amount_out) in curve {
+        let (d_in, d_out) = (amount_in - prev.0, amount_out - prev.1);
+        // A level that gave nothing back means the curve ran out of depth.
+        if d_in <= 0.0 || d_out <= 0.0 {
+            break;
+        }
+        let (price, size) = price_of(d_in, d_out);
+        levels.push(PriceLevel { price, size });
+        prev = (amount_in, amount_out);
+    }
+    levels
+}
+
+fn aggregate(levels: impl Iterator<Item = PriceLevel>, tick: f64, bids: bool) -> Vec<[f64; 2]> {
+    // Bids round down and asks round up so an aggregated level never looks better than the
+    // levels it contains.
+    let mut by_tick: BTreeMap<i64, f64> = BTreeMap::new();
+    for level in levels {
+        let ticks = level.price / tick;
+        let key = if bids { ticks.floor() } else { ticks.ceil() } as i64;
+        *by_tick.entry(key).or_default() += level.size;
+    }
+    // Round away float noise from multiplying the tick back out.
+    let agg =
+        by_tick.into_iter().map(|(key, size)| [(key as f64 * tick * 1e9).round() / 1e9, size]);
+    if bids { agg.rev().collect() } else { agg.collect() }
+}
+
+/// Builds a synthetic L2 book for every market by sampling the simulated curve in both directions.
+///
+/// Bids come from selling `levels` increments of `step_sol` SOL. Asks come from buying SOL with
+/// USDC increments sized to roughly the same amount of SOL at the best bid.
+pub fn build_book(levels: usize, step_sol: f64, tick: f64, json: bool) -> eyre::Result<()> {
+    if levels == 0 || step_sol <= 0.0 || tick <= 0.0 {
+        eyre::bail!("levels, step and tick must all be positive");
+    }
+
+    let bid_sizes: Vec<f64> = (1..=levels).map(|i| i as f64 * step_sol).collect();
+    let bid_curves = sample_curves(SwapDirection::SolToUsdc, &bid_sizes)?;
+
+    let ref_price = bid_curves
+        .values()
+        .filter_map(|curve| curve.first().map(|(sol_in, usdc_out)| usdc_out / sol_in))
+        .fold(f64::NAN, f64::max);
+    if ref_price.is_nan() {
+        eyre::bail!("could not simulate selling {step_sol} SOL on any market");
+    }
+
+    let ask_sizes: Vec<f64> = bid_sizes.iter().map(|sol| sol * ref_price).collect();
+    let ask_curves = sample_curves(SwapDirection::UsdcToSol, &ask_sizes)?;
+
+    let books: Vec<MarketBook> = config()
+        .markets()
+        .iter()
+        .map(|market| {
+            let market = market.to_string();
+            let bids = bid_curves
+                .get(&market)
+                .map(|curve| curve_to_levels(curve, |sol_in, usdc_out| (usdc_out / sol_in, sol_in)))
+                .unwrap_or_default();
+            let asks = ask_curves
+                .get(&market)
+                .map(|curve| {
+                    curve_to_levels(curve, |usdc_in, sol_out| (usdc_in / sol_out, sol_out))
+                })
+                .unwrap_or_default();
+            MarketBook { market, bids, asks }
+        })
+        .collect();
+
+    let aggregated = AggregatedBook {
+        symbol: SYMBOL.to_string(),
+        slot: FetchMetadata::read_from(&config().data_dir).map(|m| m.slot()),
+        timestamp: SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as u64,
+        bids: aggregate(books.iter().flat_map(|b| b.bids.iter().copied()), tick, true),
+        asks: aggregate(books.iter().flat_map(|b| b.asks.iter().copied()), tick, false),
+    };
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&aggregated)?);
+        return Ok(());
+    }
+
+    display_cutoffs();
+    for book in &books {
+        println!("\n--- Market: {} ---", book.market);
+        print_levels(&book.bids, &book.asks);
+    }
+    println!("\n--- Aggregated ({} markets, tick {tick}) ---", books.len());
+    let to_levels = |pairs: &[[f64; 2]]| -> Vec<PriceLevel> {
+        pairs.iter().map(|[price, size]| PriceLevel { price: *price, size: *size }).collect()
+    };
+    print_levels(&to_levels(&aggregated.bids), &to_levels(&aggregated.asks));
+
+    Ok(())
+}
+
+fn print_levels(bids: &[PriceLevel], asks: &[PriceLevel]) {
+    println!("  {:>12} {:>12} | {:<12} {:<12}", "bid size", "bid", "ask", "ask size");
+    for i in 0..bids.len().max(asks.len()) {
+        let (bid_size, bid) = bids
+            .get(i)
+            .map(|l| (format!("{:.4}", l.size), format!("{:.4}", l.price)))
+            .unwrap_or_default();
+        let (ask, ask_size) = asks
+            .get(i)
+            .map(|l| (format!("{:.4}", l.price), format!("{:.4}", l.size)))
+            .unwrap_or_default();
+        println!("  {bid_size:>12} {bid:>12} | {ask:<12} {ask_size:<12}");
+    }
+}
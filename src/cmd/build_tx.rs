@@ -0,0 +1,197 @@
+use crate::config;
+use base64::Engine;
+use base64::prelude::BASE64_STANDARD;
+use eyre::eyre;
+use litesvm::LiteSVM;
+use solana_pubkey::Pubkey;
+use solana_sdk::compute_budget::ComputeBudgetInstruction;
+use solana_sdk::hash::Hash;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::message::Message;
+use solana_transaction::Transaction;
+use solfi_sim::Simulator;
+use solfi_sim::constants::{SOL_DECIMALS, SOLFI_PROGRAM, USDC, USDC_DECIMALS, WSOL};
+use solfi_sim::rpc::RpcPool;
+use solfi_sim::simulator::{fund_user, load_snapshot_svm, swap_instructions, to_atomic};
+use solfi_sim::swap::{SwapDirection, set_min_amount_out};
+use solfi_sim::types::{ErrorProgram, SimError};
+use solfi_sim::utils::token_balance;
+use spl_associated_token_account::get_associated_token_address;
+use spl_token::instruction::close_account;
+
+/// Compute budget, ATA creation, SOL wrapping, the swap (with `min_amount_out` as the slippage
+/// guard) and unwrapping whatever WSOL is left back to SOL.
+fn build_instructions(
+    direction: SwapDirection,
+    market: &Pubkey,
+    user: &Pubkey,
+    amount_in_atomic: u64,
+    min_amount_out: u64,
+    compute_unit_limit: u32,
+    compute_unit_price: u64,
+) -> eyre::Result<Vec<Instruction>> {
+    let mut instructions =
+        vec![ComputeBudgetInstruction::set_compute_unit_limit(compute_unit_limit)];
+    if compute_unit_price > 0 {
+        instructions.push(ComputeBudgetInstruction::set_compute_unit_price(compute_unit_price));
+    }
+
+    let mut swap = swap_instructions(direction, market, user, amount_in_atomic)?;
+    if let Some(swap_ix) = swap.last_mut() {
+        set_min_amount_out(swap_ix, min_amount_out);
+    }
+    instructions.extend(swap);
+
+    let wsol_ata = get_associated_token_address(user, &WSOL);
+    instructions.push(close_account(&spl_token::id(), &wsol_ata, user, user, &[])?);
+    Ok(instructions)
+}
+
+/// Runs `instructions` on a copy of `svm` as `user` would (signatures aren't checked) and returns
+/// the change in the user's SOL and USDC balances, in atomic units, and the compute units used.
+fn simulate_as_user(
+    svm: &LiteSVM,
+    user: &Pubkey,
+    instructions: &[Instruction],
+) -> Result<(i128, i128, u64), SimError> {
+    let mut svm = svm.clone();
+    let usdc_ata = get_associated_token_address(user, &USDC);
+    let (sol_before, usdc_before) =
+        (svm.get_balance(user).unwrap_or_default(), token_balance(&svm, &usdc_ata));
+
+    let message = Message::new_with_blockhash(instructions, Some(user), &svm.latest_blockhash());
+    let meta = svm
+        .send_transaction(Transaction::new_unsigned(message))
+        .map_err(|e| SimError::from_failed_tx(&e, instructions))?;
+
+    let (sol_after, usdc_after) =
+        (svm.get_balance(user).unwrap_or_default(), token_balance(&svm, &usdc_ata));
+    Ok((
+        sol_after as i128 - sol_before as i128,
+        usdc_after as i128 - usdc_before as i128,
+        meta.compute_units_consumed,
+    ))
+}
+
+/// Checks on the snapshot that SolFi treats swap bytes 9..17 as a minimum output for this swap:
+/// the simulated output passes and one unit more fails with a SolFi error. Those bytes are
+/// undocumented, so `--slippage-bps` only relies on them once this holds (the `ix-explore`
+/// probes `expected out` and `expected out + 1`, run here for the swap being built).
+fn check_min_out_guard(
+    market: Pubkey,
+    direction: SwapDirection,
+    amount_in_atomic: u64,
+) -> eyre::Result<()> {
+    let harness =
+        Simulator::from_config(config(), None)?.harness(market, direction, amount_in_atomic)?;
+    let out = harness.run(None, None).map_err(|e| eyre!("swap fails against the snapshot: {e}"))?;
+    let to_decimals = match direction {
+        SwapDirection::SolToUsdc => USDC_DECIMALS,
+        SwapDirection::UsdcToSol => SOL_DECIMALS,
+    };
+    let expected_out = (out * 10f64.powi(to_decimals)).round() as u64;
+    let with_min_out = |min_out: u64| {
+        let mut data = harness.swap_data();
+        data[9..17].copy_from_slice(&min_out.to_le_bytes());
+        harness.run(None, Some(&data))
+    };
+
+    let at_expected = with_min_out(expected_out);
+    let above_expected = with_min_out(expected_out + 1);
+    match (&at_expected, &above_expected) {
+        (Ok(_), Err(SimError::Custom { program: ErrorProgram::Solfi, .. })) => Ok(()),
+        _ => Err(eyre!(
+            "swap bytes 9..17 don't act as a minimum output on this snapshot (min out {expected_out}: \
+             {at_expected:?}, {}: {above_expected:?}), refusing --slippage-bps",
+            expected_out + 1
+        )),
+    }
+}
+
+/// What goes into the transaction besides the swap itself.
+pub struct BuildTxOptions {
+    /// Fail the swap if it pays out more than this much less than the simulated output
+    pub slippage_bps: Option<u32>,
+    pub compute_unit_limit: u32,
+    /// Priority fee in micro-lamports per compute unit
+    pub compute_unit_price: u64,
+    /// Recent blockhash, fetched from the RPC endpoints when unset
+    pub blockhash: Option<Hash>,
+}
+
+/// Builds the unsigned swap transaction for `user`'s wallet, checks it against the local
+/// snapshot, and prints it base64-encoded on stdout. The blockhash is fetched from the RPC unless
+/// one is given.
+pub async fn build_tx(
+    rpc_urls: &[String],
+    user: Pubkey,
+    market: Pubkey,
+    direction: SwapDirection,
+    amount: f64,
+    options: &BuildTxOptions,
+) -> eyre::Result<()> {
+    let BuildTxOptions { slippage_bps, compute_unit_limit, compute_unit_price, blockhash } =
+        *options;
+    let amount_in_atomic = to_atomic(direction, amount);
+    let to_decimals = match direction {
+        SwapDirection::SolToUsdc => USDC_DECIMALS,
+        SwapDirection::UsdcToSol => SOL_DECIMALS,
+    };
+
+    let mut svm = load_snapshot_svm(&config().data_dir, None)?.with_sigverify(false);
+    svm.add_program_from_file(SOLFI_PROGRAM, &config().program_path)?;
+    fund_user(&mut svm, &user, direction, amount_in_atomic)?;
+
+    let build = |min_amount_out| {
+        build_instructions(
+            direction,
+            &market,
+            &user,
+            amount_in_atomic,
+            min_amount_out,
+            compute_unit_limit,
+            compute_unit_price,
+        )
+    };
+
+    let min_amount_out = match slippage_bps {
+        Some(bps) => {
+            check_min_out_guard(market, direction, amount_in_atomic)?;
+            // SOL comes out as lamports once the WSOL account is closed, net of the fee.
+            let (sol_change, usdc_change, _) = simulate_as_user(&svm, &user, &build(0)?)
+                .map_err(|e| eyre!("swap fails against the snapshot: {e}"))?;
+            let expected_out = match direction {
+                SwapDirection::SolToUsdc => usdc_change,
+                SwapDirection::UsdcToSol => sol_change,
+            }
+            .max(0) as f64;
+            (expected_out * (1.0 - bps as f64 / 10_000.0)).floor() as u64
+        }
+        None => 0,
+    };
+
+    let instructions = build(min_amount_out)?;
+    let (sol_change, usdc_change, compute_units) = simulate_as_user(&svm, &user, &instructions)
+        .map_err(|e| eyre!("swap fails against the snapshot: {e}"))?;
+
+    let blockhash = match blockhash {
+        Some(blockhash) => blockhash,
+        None => RpcPool::new(rpc_urls)?.get_latest_blockhash().await?,
+    };
+    let message = Message::new_with_blockhash(&instructions, Some(&user), &blockhash);
+    let tx = Transaction::new_unsigned(message);
+    println!("{}", BASE64_STANDARD.encode(bincode::serialize(&tx)?));
+
+    tracing::info!(
+        "Simulated against the snapshot as {user}: SOL {:+}, USDC {:+}, {compute_units} of \
+         {compute_unit_limit} compute units",
+        sol_change as f64 / 10f64.powi(SOL_DECIMALS),
+        usdc_change as f64 / 10f64.powi(USDC_DECIMALS)
+    );
+    if min_amount_out > 0 {
+        tracing::info!("Min out: {}", min_amount_out as f64 / 10f64.powi(to_decimals));
+    }
+    tracing::info!("Blockhash: {blockhash}");
+
+    Ok(())
+}
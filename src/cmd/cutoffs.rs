@@ -1,26 +1,24 @@
-use crate::constants::SOLFI_MARKETS;
-use crate::types::{AccountWithAddress, FetchMetadata};
-use crate::utils::u64_at_offset;
+use crate::config;
 use solana_pubkey::Pubkey;
+use solfi_sim::MarketState;
+use solfi_sim::types::{AccountWithAddress, FetchMetadata};
 
-const CUTOFF_OFFSET: usize = 488;
-const GEN_OFFSET: usize = 464;
-
-fn mm_metadata(market: &Pubkey) -> eyre::Result<(u64, u64)> {
-    let acct = AccountWithAddress::read_account(format!("data/account_{market}.json").into())?;
-    Ok((
-        u64_at_offset(acct.account.data.as_slice(), CUTOFF_OFFSET)?,
-        u64_at_offset(acct.account.data.as_slice(), GEN_OFFSET)?,
-    ))
+fn mm_metadata(market: &Pubkey) -> eyre::Result<MarketState> {
+    let acct =
+        AccountWithAddress::read_account(config().data_dir.join(format!("account_{market}.json")))?;
+    Ok(MarketState::decode(*market, &acct.account.data)?)
 }
 
 pub fn display_cutoffs() {
-    if let Some(metadata) = FetchMetadata::read() {
+    if let Some(metadata) = FetchMetadata::read_from(&config().data_dir) {
         println!("== {metadata} ==");
     }
-    for market in SOLFI_MARKETS {
-        if let Ok((cutoff, generated)) = mm_metadata(market) {
-            println!("{market} cutoff slot={cutoff}, generated slot={generated}");
+    for market in config().markets() {
+        if let Ok(state) = mm_metadata(&market) {
+            println!(
+                "{market} cutoff slot={}, generated slot={}",
+                state.cutoff_slot, state.generated_slot
+            );
         }
     }
 }
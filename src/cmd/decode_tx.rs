@@ -0,0 +1,347 @@
+use crate::config;
+use eyre::eyre;
+use serde::Serialize;
+use serde_json::Value;
+use solana_pubkey::Pubkey;
+use solana_transaction_status_client_types::option_serializer::OptionSerializer;
+use solana_transaction_status_client_types::{
+    EncodedConfirmedTransactionWithStatusMeta, EncodedTransaction, UiCompiledInstruction,
+    UiInstruction, UiLoadedAddresses, UiMessage, UiParsedInstruction, UiTransactionStatusMeta,
+    UiTransactionTokenBalance,
+};
+use solfi_sim::swap::{SWAP_ACCOUNT_ROLES, SwapData};
+use solfi_sim::{Solfi, Venue};
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Roles of the market vaults in [`SWAP_ACCOUNT_ROLES`].
+const VAULT_ROLES: [usize; 2] = [2, 3];
+
+/// An instruction with its program and accounts resolved to addresses.
+struct ResolvedInstruction {
+    program_id: Pubkey,
+    accounts: Vec<Pubkey>,
+    data: Vec<u8>,
+}
+
+#[derive(Serialize)]
+pub struct RoleAccount {
+    pub role: String,
+    pub address: String,
+}
+
+/// A market vault's token balance before and after the whole transaction, in atomic units.
+#[derive(Serialize)]
+pub struct VaultChange {
+    pub role: String,
+    pub address: String,
+    pub mint: String,
+    pub decimals: u8,
+    pub pre: i128,
+    pub post: i128,
+    pub change: i128,
+}
+
+impl VaultChange {
+    /// Converts an atomic amount of this vault's mint to UI units.
+    pub fn to_ui(&self, amount: i128) -> f64 {
+        amount as f64 / 10f64.powi(self.decimals as i32)
+    }
+}
+
+#[derive(Serialize)]
+pub struct DecodedInstruction {
+    /// Index of the top-level instruction
+    pub instruction: usize,
+    /// Position within that instruction's inner instructions, if SolFi was called through CPI
+    pub inner_index: Option<usize>,
+    pub discriminator: Option<u8>,
+    pub data: String,
+    pub swap: Option<SwapData>,
+    pub accounts: Vec<RoleAccount>,
+    pub vault_changes: Vec<VaultChange>,
+}
+
+#[derive(Serialize)]
+pub struct DecodedTx {
+    pub signature: Option<String>,
+    pub slot: u64,
+    pub block_time: Option<i64>,
+    pub error: Option<String>,
+    pub instructions: Vec<DecodedInstruction>,
+}
+
+fn hex(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn parse_pubkey(key: &str) -> eyre::Result<Pubkey> {
+    Pubkey::from_str(key).map_err(|e| eyre!("invalid address {key}: {e}"))
+}
+
+fn decode_base58(data: &str) -> eyre::Result<Vec<u8>> {
+    bs58::decode(data).into_vec().map_err(|e| eyre!("invalid instruction data: {e}"))
+}
+
+/// Resolves an instruction from the meta or a parsed message against the full account list.
+fn resolve(ix: &UiInstruction, keys: &[Pubkey]) -> eyre::Result<Option<ResolvedInstruction>> {
+    let key_at = |index: u8| {
+        keys.get(index as usize)
+            .copied()
+            .ok_or_else(|| eyre!("account index {index} is out of range"))
+    };
+    let resolved = match ix {
+        UiInstruction::Compiled(ix) => ResolvedInstruction {
+            program_id: key_at(ix.program_id_index)?,
+            accounts: ix.accounts.iter().map(|i| key_at(*i)).collect::<eyre::Result<_>>()?,
+            data: decode_base58(&ix.data)?,
+        },
+        UiInstruction::Parsed(UiParsedInstruction::PartiallyDecoded(ix)) => ResolvedInstruction {
+            program_id: parse_pubkey(&ix.program_id)?,
+            accounts: ix.accounts.iter().map(|a| parse_pubkey(a)).collect::<eyre::Result<_>>()?,
+            data: decode_base58(&ix.data)?,
+        },
+        // Fully parsed instructions belong to programs the RPC node knows, which SolFi isn't.
+        UiInstruction::Parsed(UiParsedInstruction::Parsed(_)) => return Ok(None),
+    };
+    Ok(Some(resolved))
+}
+
+/// Reads a `getTransaction` response, either the bare result or the whole JSON-RPC envelope.
+fn read_transaction(path: &Path) -> eyre::Result<EncodedConfirmedTransactionWithStatusMeta> {
+    let mut value: Value = serde_json::from_str(&fs::read_to_string(path)?)?;
+    if let Some(result) = value.get_mut("result") {
+        value = result.take();
+    }
+    if value.is_null() {
+        return Err(eyre!("{} doesn't contain a transaction", path.display()));
+    }
+    Ok(serde_json::from_value(value)?)
+}
+
+fn vault_changes(
+    vaults: &[(String, Pubkey)],
+    keys: &[Pubkey],
+    pre: &[UiTransactionTokenBalance],
+    post: &[UiTransactionTokenBalance],
+) -> Vec<VaultChange> {
+    let find = |balances: &[UiTransactionTokenBalance], vault: &Pubkey| {
+        balances.iter().find(|b| keys.get(b.account_index as usize) == Some(vault)).cloned()
+    };
+    // A vault missing from one side held nothing there; an amount that doesn't parse drops the
+    // vault rather than reporting a made-up change.
+    let raw_amount = |balance: &Option<UiTransactionTokenBalance>| match balance {
+        Some(b) => b.ui_token_amount.amount.parse::<i128>().ok(),
+        None => Some(0),
+    };
+
+    vaults
+        .iter()
+        .filter_map(|(role, vault)| {
+            let (pre, post) = (find(pre, vault), find(post, vault));
+            let known = pre.as_ref().or(post.as_ref())?;
+            let (mint, decimals) = (known.mint.clone(), known.ui_token_amount.decimals);
+            let (pre, post) = (raw_amount(&pre)?, raw_amount(&post)?);
+            Some(VaultChange {
+                role: role.clone(),
+                address: vault.to_string(),
+                mint,
+                decimals,
+                pre,
+                post,
+                change: post - pre,
+            })
+        })
+        .collect()
+}
+
+/// The signature, every account instructions can reference (static keys, then lookup table
+/// addresses) and the top-level instructions of an encoded transaction.
+fn message_parts(
+    tx: &EncodedTransaction,
+    loaded: Option<&UiLoadedAddresses>,
+) -> eyre::Result<(Option<String>, Vec<Pubkey>, Vec<UiInstruction>)> {
+    let (signature, mut keys, instructions) = match tx {
+        EncodedTransaction::Json(ui_tx) => match &ui_tx.message {
+            UiMessage::Raw(message) => (
+                ui_tx.signatures.first().cloned(),
+                message
+                    .account_keys
+                    .iter()
+                    .map(|k| parse_pubkey(k))
+                    .collect::<eyre::Result<_>>()?,
+                message.instructions.iter().cloned().map(UiInstruction::Compiled).collect(),
+            ),
+            // Parsed messages already list the lookup table accounts.
+            UiMessage::Parsed(message) => {
+                let keys = message
+                    .account_keys
+                    .iter()
+                    .map(|k| parse_pubkey(&k.pubkey))
+                    .collect::<eyre::Result<_>>()?;
+                return Ok((ui_tx.signatures.first().cloned(), keys, message.instructions.clone()));
+            }
+        },
+        encoded => {
+            let versioned =
+                encoded.decode().ok_or_else(|| eyre!("unsupported transaction encoding"))?;
+            let instructions = versioned
+                .message
+                .instructions()
+                .iter()
+                .map(|ix| UiInstruction::Compiled(UiCompiledInstruction::from(ix, None)))
+                .collect();
+            (
+                versioned.signatures.first().map(|s| s.to_string()),
+                versioned.message.static_account_keys().to_vec(),
+                instructions,
+            )
+        }
+    };
+    if let Some(loaded) = loaded {
+        for key in loaded.writable.iter().chain(&loaded.readonly) {
+            keys.push(parse_pubkey(key)?);
+        }
+    }
+    Ok((signature, keys, instructions))
+}
+
+fn decode_instruction(
+    solfi: &Solfi,
+    ix: ResolvedInstruction,
+    instruction: usize,
+    inner_index: Option<usize>,
+    keys: &[Pubkey],
+    meta: Option<&UiTransactionStatusMeta>,
+) -> DecodedInstruction {
+    let accounts: Vec<RoleAccount> = ix
+        .accounts
+        .iter()
+        .enumerate()
+        .map(|(i, address)| RoleAccount {
+            role: SWAP_ACCOUNT_ROLES.get(i).unwrap_or(&"unknown").to_string(),
+            address: address.to_string(),
+        })
+        .collect();
+    let vaults: Vec<(String, Pubkey)> = VAULT_ROLES
+        .iter()
+        .filter_map(|i| Some((SWAP_ACCOUNT_ROLES[*i].to_string(), *ix.accounts.get(*i)?)))
+        .collect();
+    let balances = |b: &OptionSerializer<Vec<UiTransactionTokenBalance>>| {
+        Option::<&Vec<_>>::from(b.as_ref()).cloned().unwrap_or_default()
+    };
+    let vault_changes = meta.map_or(vec![], |m| {
+        vault_changes(
+            &vaults,
+            keys,
+            &balances(&m.pre_token_balances),
+            &balances(&m.post_token_balances),
+        )
+    });
+
+    DecodedInstruction {
+        instruction,
+        inner_index,
+        discriminator: ix.data.first().copied(),
+        data: hex(&ix.data),
+        swap: solfi.decode_swap(&ix.data),
+        accounts,
+        vault_changes,
+    }
+}
+
+/// Finds and decodes the instructions of `solfi`'s program, top-level and inner, of a transaction
+/// saved from `getTransaction` in any of its encodings.
+pub fn read_solfi_instructions(path: &Path, solfi: &Solfi) -> eyre::Result<DecodedTx> {
+    let confirmed = read_transaction(path)?;
+    let meta = confirmed.transaction.meta.as_ref();
+    let loaded = meta.and_then(|m| Option::from(m.loaded_addresses.as_ref()));
+    let (signature, keys, outer) = message_parts(&confirmed.transaction.transaction, loaded)?;
+
+    let mut instructions = Vec::new();
+    for (index, ix) in outer.iter().enumerate() {
+        if let Some(ix) = resolve(ix, &keys)?.filter(|ix| ix.program_id == solfi.program_id()) {
+            instructions.push(decode_instruction(solfi, ix, index, None, &keys, meta));
+        }
+    }
+    let inner = meta.and_then(|m| Option::<&Vec<_>>::from(m.inner_instructions.as_ref()));
+    for group in inner.into_iter().flatten() {
+        for (inner_index, ix) in group.instructions.iter().enumerate() {
+            if let Some(ix) = resolve(ix, &keys)?.filter(|ix| ix.program_id == solfi.program_id()) {
+                let index = group.index as usize;
+                let decoded = decode_instruction(solfi, ix, index, Some(inner_index), &keys, meta);
+                instructions.push(decoded);
+            }
+        }
+    }
+    instructions.sort_by_key(|ix| (ix.instruction, ix.inner_index));
+
+    Ok(DecodedTx {
+        signature,
+        slot: confirmed.slot,
+        block_time: confirmed.block_time,
+        error: meta.and_then(|m| m.err.as_ref()).map(|e| e.to_string()),
+        instructions,
+    })
+}
+
+/// Prints the SolFi instructions of a saved transaction, see [`read_solfi_instructions`].
+pub fn decode_tx(path: &Path, json: bool) -> eyre::Result<()> {
+    let decoded = read_solfi_instructions(path, &config().solfi())?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&decoded)?);
+        return Ok(());
+    }
+
+    println!(
+        "== {} at slot {} ==",
+        decoded.signature.as_deref().unwrap_or("unsigned transaction"),
+        decoded.slot
+    );
+    if let Some(error) = &decoded.error {
+        println!("transaction failed: {error}");
+    }
+    if decoded.instructions.is_empty() {
+        println!("no SolFi instructions found");
+    }
+    for ix in &decoded.instructions {
+        match ix.inner_index {
+            Some(inner) => println!("\n--- instruction {} (inner {inner}) ---", ix.instruction),
+            None => println!("\n--- instruction {} ---", ix.instruction),
+        }
+        println!("  data: {}", ix.data);
+        match &ix.swap {
+            Some(swap) => {
+                let direction = swap
+                    .direction
+                    .map_or(format!("unknown ({})", swap.direction_byte), |d| d.to_string());
+                print!("  swap: {direction}, amount in {}", swap.amount_in);
+                match swap.unknown_9_17 {
+                    Some(bytes) => println!(", bytes 9..17 {bytes}"),
+                    None => println!(),
+                }
+                if !swap.trailing.is_empty() {
+                    println!("  trailing bytes: {}", hex(&swap.trailing));
+                }
+            }
+            None => println!("  not a swap (discriminator {:?})", ix.discriminator),
+        }
+        for account in &ix.accounts {
+            println!("  {:<20} {}", account.role, account.address);
+        }
+        for change in &ix.vault_changes {
+            println!(
+                "  {} {}: {} -> {} ({:+})",
+                change.role,
+                change.mint,
+                change.to_ui(change.pre),
+                change.to_ui(change.post),
+                change.to_ui(change.change)
+            );
+        }
+    }
+
+    Ok(())
+}
@@ -1,49 +1,111 @@
-use crate::constants::{SOLFI_MARKETS, USDC, WSOL};
-use crate::types::{AccountWithAddress, FetchMetadata};
-use solana_pubkey::Pubkey;
-use solana_rpc_client::nonblocking::rpc_client::RpcClient;
-use solana_sdk::commitment_config::CommitmentConfig;
-use spl_associated_token_account::get_associated_token_address;
+use crate::config;
+use solfi_sim::Venue;
+use solfi_sim::pubsub::{Snapshot, poll_snapshot};
+use solfi_sim::rpc::RpcPool;
+use solfi_sim::types::{AccountWithAddress, FetchMetadata};
+use solfi_sim::venue::load_venues;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
 
-pub async fn fetch_and_persist_accounts(rpc_url: String) -> eyre::Result<()> {
-    let client = RpcClient::new_with_commitment(rpc_url, CommitmentConfig::confirmed());
+pub async fn fetch_and_persist_accounts(
+    rpc_urls: &[String],
+    archive: bool,
+    venues: Option<&Path>,
+) -> eyre::Result<()> {
+    let pool = RpcPool::new(rpc_urls)?;
+    if rpc_urls.len() > 1 {
+        pool.health_check().await;
+    }
 
-    fetch_and_persist_accounts_with_client(&client).await?;
+    let slot = fetch_and_persist_accounts_with_pool(&pool, true).await?;
+    if archive {
+        archive_snapshot(slot)?;
+    }
+    if let Some(path) = venues {
+        for venue in load_venues(path)? {
+            fetch_venue_accounts(&pool, &venue).await?;
+        }
+    }
+
+    Ok(())
+}
 
+/// Fetches every account `venue` needs into its snapshot directory, or the data directory if it
+/// has none. The pool never accepts an older slot, so these are at least as recent as the SolFi
+/// accounts fetched just before.
+async fn fetch_venue_accounts(pool: &RpcPool, venue: &dyn Venue) -> eyre::Result<()> {
+    let snapshot = poll_snapshot(pool, &venue.accounts()).await?;
+    let dir = venue.snapshot_dir().unwrap_or(&config().data_dir);
+    for account in &snapshot.accounts {
+        account.save_to_dir(dir)?;
+    }
+    tracing::info!(
+        "Fetched {} accounts for {} at slot {} into {}",
+        snapshot.accounts.len(),
+        venue.name(),
+        snapshot.slot,
+        dir.display()
+    );
     Ok(())
 }
 
-pub async fn fetch_and_persist_accounts_with_client(client: &RpcClient) -> eyre::Result<()> {
-    let addresses: Vec<Pubkey> = [WSOL, USDC]
-        .into_iter()
-        .chain(SOLFI_MARKETS.iter().flat_map(|market| {
-            [
-                *market,
-                get_associated_token_address(market, &WSOL),
-                get_associated_token_address(market, &USDC),
-            ]
-        }))
-        .collect();
+/// Fetches the pool accounts into the data directory and returns the slot they were fetched at.
+/// With `chain_block_time` the block time is asked for with `getBlockTime`, otherwise the local
+/// clock is recorded, saving a request per fetch.
+pub async fn fetch_and_persist_accounts_with_pool(
+    pool: &RpcPool,
+    chain_block_time: bool,
+) -> eyre::Result<u64> {
     tracing::info!("Fetching accounts");
-    let resp = client
-        .get_multiple_accounts_with_commitment(&addresses, CommitmentConfig::processed())
-        .await?;
-    let results = resp
-        .value
-        .iter()
-        .zip(addresses)
-        .filter_map(|(account, address)| {
-            Some(AccountWithAddress { address, account: account.clone()? })
-        })
-        .collect::<Vec<_>>();
-
-    for result in &results {
-        result.save_to_file()?;
-    }
-
-    let metadata = FetchMetadata::new(resp.context.slot);
-    metadata.save_to_file()?;
+    let snapshot = poll_snapshot(pool, &config().solfi().accounts()).await?;
+    let metadata = if chain_block_time {
+        fetch_metadata(pool, snapshot.slot).await
+    } else {
+        FetchMetadata::with_local_clock(snapshot.slot, local_block_time())
+    };
+    persist_snapshot(&snapshot, &metadata)?;
     tracing::info!("Done");
 
+    Ok(snapshot.slot)
+}
+
+/// Writes the accounts of `snapshot` and its metadata into the data directory.
+pub fn persist_snapshot(snapshot: &Snapshot, metadata: &FetchMetadata) -> eyre::Result<()> {
+    let data_dir = &config().data_dir;
+    for account in &snapshot.accounts {
+        account.save_to_dir(data_dir)?;
+    }
+    metadata.save_to_dir(data_dir)?;
+    Ok(())
+}
+
+/// Processed slots usually aren't available from `getBlockTime` yet, so fall back to the local
+/// clock, which is within a slot or two of the real block time and recorded as such.
+async fn fetch_metadata(pool: &RpcPool, slot: u64) -> FetchMetadata {
+    match pool.get_block_time(slot).await {
+        Ok(block_time) => FetchMetadata::new(slot, Some(block_time)),
+        Err(e) => {
+            tracing::debug!("No block time for slot {slot}, using local clock: {e}");
+            FetchMetadata::with_local_clock(slot, local_block_time())
+        }
+    }
+}
+
+pub fn local_block_time() -> Option<i64> {
+    SystemTime::now().duration_since(UNIX_EPOCH).ok().map(|d| d.as_secs() as i64)
+}
+
+/// Copies the current snapshot into its own `<data dir>/snapshots/<slot>` directory so it can be replayed
+/// later.
+pub fn archive_snapshot(slot: u64) -> eyre::Result<()> {
+    let data_dir = &config().data_dir;
+    let dir = config().snapshots_dir().join(slot.to_string());
+    for account in AccountWithAddress::read_all_from(data_dir)? {
+        account.save_to_dir(&dir)?;
+    }
+    if let Some(metadata) = FetchMetadata::read_from(data_dir) {
+        metadata.save_to_dir(&dir)?;
+    }
+    tracing::info!("Archived snapshot to {}", dir.display());
     Ok(())
 }
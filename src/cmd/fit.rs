@@ -0,0 +1,174 @@
+use crate::cmd::{geometric_sizes, simulate};
+use crate::config;
+use eyre::eyre;
+use solfi_sim::quoter::{ApproxQuoter, CurveModel, Fit, FitKind, FittedCurve};
+use solfi_sim::swap::SwapDirection;
+use solfi_sim::types::FetchMetadata;
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::Instant;
+
+/// Simulated (amount in, amount out) pairs per market.
+fn sample(
+    direction: SwapDirection,
+    sizes: &[f64],
+) -> eyre::Result<HashMap<String, Vec<(f64, f64)>>> {
+    let mut samples: HashMap<String, Vec<(f64, f64)>> = HashMap::new();
+    for size in sizes {
+        for result in simulate(direction, Some(*size), None, true, false)? {
+            if let Some(out) = result.out_amount {
+                samples.entry(result.market).or_default().push((result.in_amount, out));
+            }
+        }
+    }
+    Ok(samples)
+}
+
+/// Samples every market's curve in both directions, fits `kind` to it and saves the model to
+/// `output`. Fit error is measured against extra simulations at the geometric midpoints between
+/// sampled sizes, which the fit never saw.
+pub fn fit_curves(
+    points: usize,
+    kind: FitKind,
+    degree: usize,
+    min_sol: f64,
+    max_sol: f64,
+    output: &Path,
+) -> eyre::Result<()> {
+    if points < 2 || min_sol <= 0.0 || max_sol <= min_sol {
+        eyre::bail!("need at least 2 points and 0 < min-sol < max-sol");
+    }
+    let slot = FetchMetadata::read_from(&config().data_dir)
+        .map(|m| m.slot())
+        .ok_or_else(|| eyre!("no metadata.json"))?;
+
+    let sol_sizes = geometric_sizes(min_sol, max_sol, points);
+    let sell_samples = sample(SwapDirection::SolToUsdc, &sol_sizes)?;
+    let ref_price = sell_samples
+        .values()
+        .filter_map(|s| s.first().map(|(sol_in, usdc_out)| usdc_out / sol_in))
+        .fold(f64::NAN, f64::max);
+    if ref_price.is_nan() {
+        eyre::bail!("could not simulate selling {min_sol} SOL on any market");
+    }
+    let usdc_sizes: Vec<f64> = sol_sizes.iter().map(|sol| sol * ref_price).collect();
+    let buy_samples = sample(SwapDirection::UsdcToSol, &usdc_sizes)?;
+
+    let mut curves = Vec::new();
+    for (direction, samples) in
+        [(SwapDirection::SolToUsdc, &sell_samples), (SwapDirection::UsdcToSol, &buy_samples)]
+    {
+        for market in config().markets() {
+            let market = market.to_string();
+            let Some(market_samples) = samples.get(&market) else {
+                tracing::warn!("No successful {direction} samples for {market}");
+                continue;
+            };
+            let fit = match Fit::from_samples(kind, degree, market_samples) {
+                Ok(fit) => fit,
+                Err(e) => {
+                    tracing::warn!("Could not fit {direction} curve for {market}: {e}");
+                    continue;
+                }
+            };
+            let (min_in, max_in) = market_samples
+                .iter()
+                .fold((f64::MAX, f64::MIN), |(lo, hi), (x, _)| (lo.min(*x), hi.max(*x)));
+            curves.push(FittedCurve {
+                market,
+                direction,
+                min_in,
+                max_in,
+                fit,
+                max_error_bps: None,
+                mean_error_bps: None,
+            });
+        }
+    }
+
+    let model = measure_errors(CurveModel { slot, kind, curves }, &sol_sizes, &usdc_sizes)?;
+
+    println!("== fitted {kind:?} curves at slot {slot} ==");
+    for curve in &model.curves {
+        println!(
+            "{} {} in=[{:.4}, {:.4}] max err={:.3} bps, mean err={:.3} bps",
+            curve.market,
+            curve.direction,
+            curve.min_in,
+            curve.max_in,
+            curve.max_error_bps.unwrap_or(f64::NAN),
+            curve.mean_error_bps.unwrap_or(f64::NAN),
+        );
+    }
+    model.save_to_file(output)?;
+    println!("Saved model to {}", output.display());
+
+    Ok(())
+}
+
+fn midpoints(sizes: &[f64]) -> Vec<f64> {
+    sizes.windows(2).map(|w| (w[0] * w[1]).sqrt()).collect()
+}
+
+fn measure_errors(
+    model: CurveModel,
+    sol_sizes: &[f64],
+    usdc_sizes: &[f64],
+) -> eyre::Result<CurveModel> {
+    let held_out = [
+        (SwapDirection::SolToUsdc, sample(SwapDirection::SolToUsdc, &midpoints(sol_sizes))?),
+        (SwapDirection::UsdcToSol, sample(SwapDirection::UsdcToSol, &midpoints(usdc_sizes))?),
+    ];
+
+    let mut errors: HashMap<(String, SwapDirection), Vec<f64>> = HashMap::new();
+    let quoter = ApproxQuoter::new(model);
+    for (direction, samples) in &held_out {
+        for (market, points) in samples {
+            for (amount_in, actual) in points {
+                if let Some(approx) = quoter.quote(market, *direction, *amount_in) {
+                    let err_bps = (approx - actual).abs() / actual * 10_000.0;
+                    errors.entry((market.clone(), *direction)).or_default().push(err_bps);
+                }
+            }
+        }
+    }
+
+    let mut model = quoter.into_model();
+    for curve in &mut model.curves {
+        if let Some(errs) = errors.get(&(curve.market.clone(), curve.direction)) {
+            curve.max_error_bps = errs.iter().copied().reduce(f64::max);
+            curve.mean_error_bps = Some(errs.iter().sum::<f64>() / errs.len() as f64);
+        }
+    }
+    Ok(model)
+}
+
+/// Quotes every market from a saved curve model instead of the SVM.
+pub fn approx_quote(model: &Path, direction: SwapDirection, amount: f64) -> eyre::Result<()> {
+    let quoter = ApproxQuoter::load(model)?;
+    if let Some(metadata) = FetchMetadata::read_from(&config().data_dir)
+        && quoter.is_stale(metadata.slot())
+    {
+        tracing::warn!(
+            "Model was fitted at slot {} but the snapshot is at slot {}, re-run `fit`",
+            quoter.slot(),
+            metadata.slot()
+        );
+    }
+
+    for market in config().markets() {
+        let market = market.to_string();
+        let start = Instant::now();
+        let out = quoter.quote(&market, direction, amount);
+        let elapsed = start.elapsed();
+        match out {
+            Some(out) => println!("{market},{amount},{out},{}ns", elapsed.as_nanos()),
+            None => println!("{market},{amount},,out of fitted range"),
+        }
+    }
+    if let Some((market, out)) = quoter.best_quote(direction, amount) {
+        println!("best: {market},{amount},{out}");
+    }
+
+    Ok(())
+}
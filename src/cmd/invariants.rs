@@ -0,0 +1,45 @@
+use crate::cmd::{display_cutoffs, geometric_sizes};
+use crate::config;
+use eyre::eyre;
+use solfi_sim::Simulator;
+use solfi_sim::invariants::{Invariant, InvariantOptions, check_invariants as check};
+
+/// Checks every market's curve in the current snapshot at `steps` SOL sizes between `min_sol`
+/// and `max_sol` and prints which markets and sizes break each invariant. Fails if any does.
+pub fn check_invariants(
+    min_sol: f64,
+    max_sol: f64,
+    steps: usize,
+    max_spread_bps: f64,
+    tolerance_bps: f64,
+    json: bool,
+) -> eyre::Result<()> {
+    let sizes = geometric_sizes(min_sol, max_sol, steps);
+    let simulator = Simulator::from_config(config(), None)?;
+    let violations = check(&simulator, &sizes, InvariantOptions { max_spread_bps, tolerance_bps })?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&violations)?);
+    } else {
+        display_cutoffs();
+        println!(
+            "\nChecking {} sizes between {min_sol} and {max_sol} SOL on {} markets...\n",
+            sizes.len(),
+            simulator.markets().len()
+        );
+        for invariant in Invariant::ALL {
+            let broken: Vec<_> = violations.iter().filter(|v| v.invariant == invariant).collect();
+            let status = if broken.is_empty() { "ok" } else { "BROKEN" };
+            println!("--- {invariant}: {} ({status}) ---", invariant.description());
+            for v in broken {
+                println!("  {} {} {:<12} {}", v.market, v.direction, v.size, v.detail);
+            }
+        }
+    }
+
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(eyre!("{} invariant violations", violations.len()))
+    }
+}
@@ -0,0 +1,157 @@
+use crate::config;
+use csv::WriterBuilder;
+use eyre::eyre;
+use serde::Serialize;
+use solana_pubkey::Pubkey;
+use solfi_sim::Simulator;
+use solfi_sim::constants::{SOL_DECIMALS, USDC_DECIMALS};
+use solfi_sim::simulator::to_atomic;
+use solfi_sim::swap::SwapDirection;
+use std::collections::BTreeMap;
+use std::io::stdout;
+
+/// Offset of the undocumented bytes between the amount and the direction.
+const UNKNOWN_START: usize = 9;
+const DIRECTION_OFFSET: usize = 17;
+
+struct Probe {
+    name: String,
+    data: Vec<u8>,
+}
+
+#[derive(Serialize)]
+struct ProbeResult {
+    probe: String,
+    data: String,
+    outcome: &'static str,
+    out_amount: Option<f64>,
+    change_bps: Option<f64>,
+    error: Option<String>,
+    error_kind: Option<String>,
+}
+
+fn hex(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Instruction payloads to try: values in bytes 9..17 (read as one u64 around the expected output
+/// in case it's a minimum out, then byte by byte in case they're flags), odd direction bytes,
+/// truncated and extended payloads, and every other discriminator with the swap payload.
+fn probes(swap_data: &[u8], expected_out: u64) -> Vec<Probe> {
+    let mut probes = vec![Probe { name: "baseline".into(), data: swap_data.to_vec() }];
+    let with = |offset: usize, bytes: &[u8]| {
+        let mut data = swap_data.to_vec();
+        data[offset..offset + bytes.len()].copy_from_slice(bytes);
+        data
+    };
+
+    for (label, value) in [
+        ("1", 1),
+        ("expected out - 1", expected_out.saturating_sub(1)),
+        ("expected out", expected_out),
+        ("expected out + 1", expected_out + 1),
+        ("2x expected out", expected_out * 2),
+        ("u64 max", u64::MAX),
+    ] {
+        probes.push(Probe {
+            name: format!("u64 at {UNKNOWN_START} = {label}"),
+            data: with(UNKNOWN_START, &value.to_le_bytes()),
+        });
+    }
+    for offset in UNKNOWN_START..DIRECTION_OFFSET {
+        for byte in [0x01, 0xff] {
+            probes.push(Probe {
+                name: format!("byte {offset} = {byte:#04x}"),
+                data: with(offset, &[byte]),
+            });
+        }
+    }
+    for byte in [2, 0xff] {
+        probes.push(Probe {
+            name: format!("direction = {byte:#04x}"),
+            data: with(DIRECTION_OFFSET, &[byte]),
+        });
+    }
+    for len in [1, UNKNOWN_START, DIRECTION_OFFSET] {
+        probes.push(Probe {
+            name: format!("truncated to {len} bytes"),
+            data: swap_data[..len].to_vec(),
+        });
+    }
+    let mut extended = swap_data.to_vec();
+    extended.extend([0; 8]);
+    probes.push(Probe { name: format!("extended to {} bytes", extended.len()), data: extended });
+
+    for discriminator in 0..=u8::MAX {
+        if discriminator == swap_data[0] {
+            continue;
+        }
+        probes.push(Probe {
+            name: format!("discriminator {discriminator}"),
+            data: with(0, &[discriminator]),
+        });
+    }
+    probes
+}
+
+/// Sends controlled variations of the swap instruction data through the SVM and groups the
+/// outcomes, to help map the parts of the instruction interface that aren't known yet.
+pub fn explore_instruction(
+    market: Pubkey,
+    direction: SwapDirection,
+    amount: f64,
+) -> eyre::Result<()> {
+    let harness = Simulator::from_config(config(), None)?.harness(
+        market,
+        direction,
+        to_atomic(direction, amount),
+    )?;
+    let swap_data = harness.swap_data();
+    let baseline = harness.run(None, None).map_err(|e| eyre!("baseline swap failed: {e}"))?;
+    let to_decimals = match direction {
+        SwapDirection::SolToUsdc => USDC_DECIMALS,
+        SwapDirection::UsdcToSol => SOL_DECIMALS,
+    };
+    let expected_out = (baseline * 10f64.powi(to_decimals)).round() as u64;
+
+    let mut wtr = WriterBuilder::new().from_writer(stdout());
+    let mut groups: BTreeMap<String, Vec<String>> = BTreeMap::new();
+
+    for probe in probes(&swap_data, expected_out) {
+        let result = harness.run(None, Some(&probe.data));
+        let (outcome, out_amount, error) = match result {
+            Ok(out) if out == baseline => ("unchanged", Some(out), None),
+            Ok(out) => ("output changed", Some(out), None),
+            Err(err) => ("error", None, Some(err)),
+        };
+        let group = match &error {
+            Some(err) => format!("error: {}", err.label()),
+            None => outcome.to_string(),
+        };
+        groups.entry(group).or_default().push(probe.name.clone());
+
+        wtr.serialize(ProbeResult {
+            probe: probe.name,
+            data: hex(&probe.data),
+            outcome,
+            out_amount,
+            change_bps: out_amount
+                .filter(|_| baseline > 0.0)
+                .map(|out| (out / baseline - 1.0) * 10_000.0),
+            error: error.as_ref().map(|e| e.to_string()),
+            error_kind: error.as_ref().map(|e| e.label()),
+        })?;
+    }
+    wtr.flush()?;
+
+    tracing::info!("baseline: {amount} {direction} on {market} -> {baseline}");
+    for (group, probes) in groups {
+        let mut names = probes.iter().take(12).cloned().collect::<Vec<_>>().join(", ");
+        if probes.len() > 12 {
+            names.push_str(&format!(" ... and {} more", probes.len() - 12));
+        }
+        tracing::info!("{group} ({} probes): {names}", probes.len());
+    }
+
+    Ok(())
+}
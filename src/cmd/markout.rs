@@ -0,0 +1,211 @@
+use crate::cmd::simulate_in;
+use crate::config;
+use csv::{ReaderBuilder, WriterBuilder};
+use eyre::eyre;
+use serde::Serialize;
+use solfi_sim::constants::SLOT_DURATION_MS;
+use solfi_sim::swap::SwapDirection;
+use solfi_sim::types::{BlockTimeSource, FetchMetadata};
+use std::fs;
+use std::io::stdout;
+use std::path::{Path, PathBuf};
+
+/// Timestamps above this are treated as milliseconds rather than seconds.
+const MILLIS_THRESHOLD: f64 = 1e11;
+
+#[derive(Serialize)]
+struct MarkoutRow {
+    slot: u64,
+    block_time: i64,
+    block_time_source: Option<BlockTimeSource>,
+    market: String,
+    direction: SwapDirection,
+    size_sol: f64,
+    quote_price: f64,
+    reference_mid: f64,
+    edge_bps: f64,
+    horizon_slots: u64,
+    markout_mid: Option<f64>,
+    markout_bps: Option<f64>,
+}
+
+/// Reference mid prices sorted by time, in unix seconds.
+struct ReferencePrices(Vec<(f64, f64)>);
+
+impl ReferencePrices {
+    /// Reads a `timestamp,price` CSV with an optional header. Timestamps may be unix seconds or
+    /// milliseconds. Any other row that doesn't parse is an error.
+    fn read(path: &Path) -> eyre::Result<Self> {
+        let mut rdr = ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .trim(csv::Trim::All)
+            .from_path(path)?;
+        let mut prices = Vec::new();
+        for (i, record) in rdr.records().enumerate() {
+            let record = record?;
+            let parsed = record
+                .get(0)
+                .zip(record.get(1))
+                .and_then(|(ts, px)| Some((ts.parse::<f64>().ok()?, px.parse::<f64>().ok()?)))
+                .filter(|(ts, px)| ts.is_finite() && px.is_finite());
+            match parsed {
+                Some((ts, price)) => {
+                    let ts = if ts > MILLIS_THRESHOLD { ts / 1000.0 } else { ts };
+                    prices.push((ts, price));
+                }
+                None if i == 0 => {}
+                None => {
+                    let row: Vec<&str> = record.iter().collect();
+                    return Err(eyre!(
+                        "{} row {}: expected `timestamp,price`, got `{}`",
+                        path.display(),
+                        i + 1,
+                        row.join(",")
+                    ));
+                }
+            }
+        }
+        if prices.is_empty() {
+            return Err(eyre!("no reference prices found in {}", path.display()));
+        }
+        prices.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Ok(Self(prices))
+    }
+
+    /// Latest reference price at or before `ts`. Times past the end of the file have no price,
+    /// since the last mid would be stale.
+    fn as_of(&self, ts: f64) -> Option<f64> {
+        if self.0.last().is_some_and(|(last, _)| ts > *last) {
+            return None;
+        }
+        let idx = self.0.partition_point(|(t, _)| *t <= ts);
+        if idx == 0 { None } else { Some(self.0[idx - 1].1) }
+    }
+}
+
+/// Every snapshot archived under the data directory.
+pub fn default_snapshot_dirs() -> eyre::Result<Vec<PathBuf>> {
+    let root = config().snapshots_dir();
+    if !root.exists() {
+        return Ok(vec![]);
+    }
+    let mut dirs = Vec::new();
+    for entry in fs::read_dir(&root)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            dirs.push(path);
+        }
+    }
+    Ok(dirs)
+}
+
+/// Positive edge means the quote is worse than the reference mid for the taker.
+fn edge_bps(direction: SwapDirection, quote_price: f64, mid: f64) -> f64 {
+    match direction {
+        SwapDirection::SolToUsdc => (mid - quote_price) / mid * 10_000.0,
+        SwapDirection::UsdcToSol => (quote_price - mid) / mid * 10_000.0,
+    }
+}
+
+/// Marks SolFi quotes from archived snapshots against reference mids. For every snapshot, size and
+/// direction it reports the quote's edge against the mid at the snapshot's block time, and the
+/// same edge against the mid `N` slots later for each horizon. Snapshots timed by the local clock
+/// rather than the chain are skipped unless `local_clock` is set.
+pub fn markout(
+    reference: PathBuf,
+    snapshots: Vec<PathBuf>,
+    sizes: Vec<f64>,
+    horizons: Vec<u64>,
+    local_clock: bool,
+) -> eyre::Result<()> {
+    let reference = ReferencePrices::read(&reference)?;
+    let snapshots = if snapshots.is_empty() { default_snapshot_dirs()? } else { snapshots };
+    if snapshots.is_empty() {
+        return Err(eyre!(
+            "no snapshots to mark, archive some with `fetch-accounts --archive` or pass --snapshots"
+        ));
+    }
+
+    let mut wtr = WriterBuilder::new().has_headers(true).from_writer(stdout());
+    let mut snapshots: Vec<(PathBuf, FetchMetadata)> = snapshots
+        .into_iter()
+        .filter_map(|dir| {
+            let metadata = FetchMetadata::read_from(&dir);
+            if metadata.is_none() {
+                tracing::warn!("Skipping {}: no metadata.json", dir.display());
+            }
+            Some((dir, metadata?))
+        })
+        .collect();
+    snapshots.sort_by_key(|(_, metadata)| metadata.slot());
+
+    for (dir, metadata) in snapshots {
+        let Some(block_time) = metadata.block_time() else {
+            tracing::warn!("Skipping {}: no block time recorded", dir.display());
+            continue;
+        };
+        let block_time_source = metadata.block_time_source();
+        match block_time_source {
+            Some(BlockTimeSource::LocalClock) if !local_clock => {
+                tracing::warn!(
+                    "Skipping {}: block time is from the local clock, pass --local-clock to use it",
+                    dir.display()
+                );
+                continue;
+            }
+            Some(BlockTimeSource::LocalClock) => tracing::warn!(
+                "{}: block time is from the local clock, markouts are approximate",
+                dir.display()
+            ),
+            None => tracing::warn!("{}: block time source unknown", dir.display()),
+            Some(BlockTimeSource::Rpc) => {}
+        }
+        let Some(mid) = reference.as_of(block_time as f64) else {
+            tracing::warn!("Skipping {}: no reference price at {block_time}", dir.display());
+            continue;
+        };
+
+        for &size_sol in &sizes {
+            for direction in [SwapDirection::SolToUsdc, SwapDirection::UsdcToSol] {
+                // Buys are sized in USDC, so aim for `size_sol` at the reference mid.
+                let amount_in = match direction {
+                    SwapDirection::SolToUsdc => size_sol,
+                    SwapDirection::UsdcToSol => size_sol * mid,
+                };
+                let results = simulate_in(&dir, direction, Some(amount_in), None, true, false)?;
+                for result in results {
+                    let Some(out) = result.out_amount.filter(|out| *out > 0.0) else {
+                        continue;
+                    };
+                    let quote_price = match direction {
+                        SwapDirection::SolToUsdc => out / amount_in,
+                        SwapDirection::UsdcToSol => amount_in / out,
+                    };
+                    for &horizon_slots in &horizons {
+                        let ts =
+                            block_time as f64 + (horizon_slots * SLOT_DURATION_MS) as f64 / 1000.0;
+                        let markout_mid = reference.as_of(ts);
+                        wtr.serialize(MarkoutRow {
+                            slot: metadata.slot(),
+                            block_time,
+                            block_time_source,
+                            market: result.market.clone(),
+                            direction,
+                            size_sol,
+                            quote_price,
+                            reference_mid: mid,
+                            edge_bps: edge_bps(direction, quote_price, mid),
+                            horizon_slots,
+                            markout_mid,
+                            markout_bps: markout_mid.map(|m| edge_bps(direction, quote_price, m)),
+                        })?;
+                    }
+                }
+            }
+        }
+        wtr.flush()?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,20 @@
+use crate::config;
+use solfi_sim::mock_rpc::{MockRpc, MockScript};
+use std::path::Path;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+
+/// Serves the snapshot in the data directory as a Solana RPC node on `port` until killed.
+pub async fn serve_mock_rpc(port: u16, script: Option<&Path>) -> eyre::Result<()> {
+    let script = script.map(MockScript::load).transpose()?.unwrap_or_default();
+    let mock = MockRpc::from_dir(&config().data_dir, script)?;
+    let listener = TcpListener::bind(format!("127.0.0.1:{port}")).await?;
+    println!(
+        "Serving {} at slot {} on http://{}",
+        config().data_dir.display(),
+        mock.slot(),
+        listener.local_addr()?
+    );
+    Arc::new(mock).serve(listener).await?;
+    Ok(())
+}
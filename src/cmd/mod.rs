@@ -1,9 +1,40 @@
+mod arb;
+mod book;
+mod build_tx;
 mod cutoffs;
+mod decode_tx;
 mod fetch;
+mod fit;
+mod invariants;
+mod ix_explore;
+mod markout;
+#[cfg(feature = "mock-rpc")]
+mod mock_rpc;
+mod plot;
+mod sensitivity;
 mod simulate;
 mod spreads;
+mod trace;
+mod validate;
 
+pub use arb::{ArbOpportunity, detect_arbs, find_arbs, geometric_sizes};
+pub use book::build_book;
+pub use build_tx::{BuildTxOptions, build_tx};
 pub use cutoffs::display_cutoffs;
-pub use fetch::{fetch_and_persist_accounts, fetch_and_persist_accounts_with_client};
-pub use simulate::simulate;
-pub use spreads::calculate_spread;
+pub use decode_tx::{decode_tx, read_solfi_instructions};
+pub use fetch::{
+    fetch_and_persist_accounts, fetch_and_persist_accounts_with_pool, local_block_time,
+    persist_snapshot,
+};
+pub use fit::{approx_quote, fit_curves};
+pub use invariants::check_invariants;
+pub use ix_explore::explore_instruction;
+pub use markout::{default_snapshot_dirs, markout};
+#[cfg(feature = "mock-rpc")]
+pub use mock_rpc::serve_mock_rpc;
+pub use plot::{ChartKind, plot};
+pub use sensitivity::{Perturbation, SensitivityOptions, sensitivity};
+pub use simulate::{SimulateOptions, simulate, simulate_in, simulate_with};
+pub use spreads::{SpreadAnalysis, calculate_spread, spreads};
+pub use trace::trace_swap;
+pub use validate::validate;
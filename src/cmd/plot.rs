@@ -0,0 +1,295 @@
+use crate::cmd::{default_snapshot_dirs, geometric_sizes};
+use crate::config;
+use clap::ValueEnum;
+use eyre::eyre;
+use plotters::coord::Shift;
+use plotters::coord::ranged1d::{AsRangedCoord, ValueFormatter};
+use plotters::prelude::*;
+use plotters::style::{FontStyle, register_font};
+use solana_pubkey::Pubkey;
+use solfi_sim::Simulator;
+use solfi_sim::swap::SwapDirection;
+use solfi_sim::types::FetchMetadata;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+const SIZE: (u32, u32) = (1280, 800);
+const FONT: &str = "sans-serif";
+
+/// Bundled so PNGs render text without depending on system fonts.
+static DEJAVU_SANS: &[u8] = include_bytes!("../../static/fonts/DejaVuSans.ttf");
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum ChartKind {
+    /// Output against input
+    Curves,
+    /// Effective price against size
+    Price,
+    /// Spread between buying and selling against size
+    Spread,
+    /// Effective price of one size across archived snapshots
+    History,
+}
+
+impl fmt::Display for ChartKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChartKind::Curves => write!(f, "curves"),
+            ChartKind::Price => write!(f, "price"),
+            ChartKind::Spread => write!(f, "spread"),
+            ChartKind::History => write!(f, "history"),
+        }
+    }
+}
+
+/// One line per market.
+struct Chart {
+    title: String,
+    x_desc: String,
+    y_desc: String,
+    /// Sizes span orders of magnitude, but output against input reads best on a linear axis
+    log_x: bool,
+    series: Vec<(String, Vec<(f64, f64)>)>,
+}
+
+/// USDC per SOL of swapping `amount_in` for `amount_out`.
+fn price(direction: SwapDirection, amount_in: f64, amount_out: f64) -> f64 {
+    match direction {
+        SwapDirection::SolToUsdc => amount_out / amount_in,
+        SwapDirection::UsdcToSol => amount_in / amount_out,
+    }
+}
+
+/// Output of swapping `amount` on `market`, if the swap succeeds.
+fn quote(
+    simulator: &Simulator,
+    market: &Pubkey,
+    direction: SwapDirection,
+    amount: f64,
+) -> Option<f64> {
+    simulator.quote(market, direction, amount).ok().filter(|out| *out > 0.0)
+}
+
+/// Best price selling `sol` on any market, used to size buys in USDC.
+fn reference_price(simulator: &Simulator, markets: &[Pubkey], sol: f64) -> eyre::Result<f64> {
+    markets
+        .iter()
+        .filter_map(|market| quote(simulator, market, SwapDirection::SolToUsdc, sol))
+        .map(|usdc| usdc / sol)
+        .reduce(f64::max)
+        .ok_or_else(|| eyre!("could not simulate selling {sol} SOL on any market"))
+}
+
+fn size_chart(kind: ChartKind, direction: SwapDirection, sol_sizes: &[f64]) -> eyre::Result<Chart> {
+    let simulator = Simulator::from_config(config(), None)?;
+    let markets = config().markets();
+    let ref_price = reference_price(&simulator, &markets, sol_sizes[0])?;
+    let amount_in = |direction, sol: f64| match direction {
+        SwapDirection::SolToUsdc => sol,
+        SwapDirection::UsdcToSol => sol * ref_price,
+    };
+
+    let mut series = vec![];
+    for market in &markets {
+        let points = sol_sizes
+            .iter()
+            .filter_map(|&sol| match kind {
+                ChartKind::Curves => {
+                    let amount_in = amount_in(direction, sol);
+                    Some((amount_in, quote(&simulator, market, direction, amount_in)?))
+                }
+                ChartKind::Price => {
+                    let amount_in = amount_in(direction, sol);
+                    let out = quote(&simulator, market, direction, amount_in)?;
+                    Some((sol, price(direction, amount_in, out)))
+                }
+                ChartKind::Spread => {
+                    let usdc_in = amount_in(SwapDirection::UsdcToSol, sol);
+                    let usdc_out = quote(&simulator, market, SwapDirection::SolToUsdc, sol)?;
+                    let sol_out = quote(&simulator, market, SwapDirection::UsdcToSol, usdc_in)?;
+                    let bid = price(SwapDirection::SolToUsdc, sol, usdc_out);
+                    let ask = price(SwapDirection::UsdcToSol, usdc_in, sol_out);
+                    Some((sol, (ask - bid) / ((ask + bid) / 2.0) * 10_000.0))
+                }
+                ChartKind::History => unreachable!("history isn't drawn against size"),
+            })
+            .collect();
+        series.push((market.to_string(), points));
+    }
+
+    let slot = simulator.slot();
+    let (title, x_desc, y_desc) = match kind {
+        ChartKind::Curves => {
+            let (token_in, token_out) = match direction {
+                SwapDirection::SolToUsdc => ("SOL", "USDC"),
+                SwapDirection::UsdcToSol => ("USDC", "SOL"),
+            };
+            (
+                format!("SolFi {direction} output against input, slot {slot}"),
+                format!("{token_in} in"),
+                format!("{token_out} out"),
+            )
+        }
+        ChartKind::Price => (
+            format!("SolFi {direction} effective price, slot {slot}"),
+            "size (SOL)".to_string(),
+            "USDC per SOL".to_string(),
+        ),
+        _ => (
+            format!("SolFi spread, slot {slot}"),
+            "size (SOL)".to_string(),
+            "spread (bps)".to_string(),
+        ),
+    };
+    Ok(Chart { title, x_desc, y_desc, log_x: kind != ChartKind::Curves, series })
+}
+
+fn history_chart(direction: SwapDirection, size_sol: f64) -> eyre::Result<Chart> {
+    let mut snapshots: Vec<(PathBuf, u64)> = default_snapshot_dirs()?
+        .into_iter()
+        .filter_map(|dir| {
+            let slot = FetchMetadata::read_from(&dir)?.slot();
+            Some((dir, slot))
+        })
+        .collect();
+    if snapshots.is_empty() {
+        return Err(eyre!("no archived snapshots, archive some with `fetch-accounts --archive`"));
+    }
+    snapshots.sort_by_key(|(_, slot)| *slot);
+
+    let markets = config().markets();
+    let mut series: Vec<(String, Vec<(f64, f64)>)> =
+        markets.iter().map(|market| (market.to_string(), vec![])).collect();
+    for (dir, slot) in &snapshots {
+        let simulator = Simulator::load_with(dir, None, config().solfi())?;
+        let amount_in = match direction {
+            SwapDirection::SolToUsdc => size_sol,
+            SwapDirection::UsdcToSol => size_sol * reference_price(&simulator, &markets, size_sol)?,
+        };
+        for (market, (_, points)) in markets.iter().zip(&mut series) {
+            if let Some(out) = quote(&simulator, market, direction, amount_in) {
+                points.push((*slot as f64, price(direction, amount_in, out)));
+            }
+        }
+    }
+
+    let (first, last) = (snapshots[0].1, snapshots[snapshots.len() - 1].1);
+    Ok(Chart {
+        title: format!("SolFi {direction} price for {size_sol} SOL, slots {first}-{last}"),
+        x_desc: "slot".to_string(),
+        y_desc: "USDC per SOL".to_string(),
+        log_x: false,
+        series,
+    })
+}
+
+/// Padded range of every y value, so lines don't sit on the frame.
+fn y_range(chart: &Chart) -> eyre::Result<(f64, f64)> {
+    let (min, max) = chart
+        .series
+        .iter()
+        .flat_map(|(_, points)| points.iter().map(|(_, y)| *y))
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), y| (lo.min(y), hi.max(y)));
+    if min > max {
+        return Err(eyre!("nothing to plot, no market could be quoted"));
+    }
+    let pad = ((max - min) * 0.05).max(max.abs() * 1e-6).max(1e-9);
+    Ok((min - pad, max + pad))
+}
+
+fn x_range(chart: &Chart) -> (f64, f64) {
+    let (min, max) = chart
+        .series
+        .iter()
+        .flat_map(|(_, points)| points.iter().map(|(x, _)| *x))
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), x| (lo.min(x), hi.max(x)));
+    if min < max { (min, max) } else { (min - 1.0, max + 1.0) }
+}
+
+fn draw_on<DB, X>(root: &DrawingArea<DB, Shift>, chart: &Chart, x: X) -> eyre::Result<()>
+where
+    DB: DrawingBackend,
+    DB::ErrorType: 'static,
+    X: AsRangedCoord<Value = f64>,
+    X::CoordDescType: ValueFormatter<f64>,
+{
+    let (y_min, y_max) = y_range(chart)?;
+    root.fill(&WHITE)?;
+    let mut ctx = ChartBuilder::on(root)
+        .caption(&chart.title, (FONT, 26))
+        .margin(20)
+        .margin_right(50)
+        .x_label_area_size(50)
+        .y_label_area_size(90)
+        .build_cartesian_2d(x, y_min..y_max)?;
+    ctx.configure_mesh()
+        .x_desc(&chart.x_desc)
+        .y_desc(&chart.y_desc)
+        .label_style((FONT, 15))
+        .x_label_formatter(&|x| format!("{x}"))
+        .draw()?;
+    for (i, (market, points)) in chart.series.iter().enumerate() {
+        let color = Palette99::pick(i).to_rgba();
+        ctx.draw_series(LineSeries::new(points.iter().copied(), color.stroke_width(2)))?
+            .label(market)
+            .legend(move |(x, y)| PathElement::new([(x, y), (x + 20, y)], color.stroke_width(2)));
+    }
+    ctx.configure_series_labels()
+        .label_font((FONT, 15))
+        .background_style(WHITE.mix(0.8))
+        .border_style(BLACK)
+        .draw()?;
+    root.present()?;
+    Ok(())
+}
+
+fn draw<DB>(root: DrawingArea<DB, Shift>, chart: &Chart) -> eyre::Result<()>
+where
+    DB: DrawingBackend,
+    DB::ErrorType: 'static,
+{
+    let (x_min, x_max) = x_range(chart);
+    if chart.log_x {
+        draw_on(&root, chart, (x_min..x_max).log_scale())
+    } else {
+        draw_on(&root, chart, x_min..x_max)
+    }
+}
+
+/// Draws `kind` for every configured market to `output`, as SVG if the file name ends in `.svg`
+/// and PNG otherwise.
+pub fn plot(
+    kind: ChartKind,
+    direction: SwapDirection,
+    min_sol: f64,
+    max_sol: f64,
+    points: usize,
+    size: f64,
+    output: Option<PathBuf>,
+) -> eyre::Result<()> {
+    if min_sol <= 0.0 || max_sol < min_sol {
+        eyre::bail!("need 0 < min-sol <= max-sol");
+    }
+    register_font(FONT, FontStyle::Normal, DEJAVU_SANS)
+        .map_err(|_| eyre!("bundled font is invalid"))?;
+
+    let chart = match kind {
+        ChartKind::History => history_chart(direction, size)?,
+        _ => size_chart(kind, direction, &geometric_sizes(min_sol, max_sol, points))?,
+    };
+    let output = match output {
+        Some(output) => output,
+        None => {
+            let slot = FetchMetadata::read_from(&config().data_dir).map(|m| m.slot());
+            PathBuf::from(format!("{kind}_{}.png", slot.unwrap_or_default()))
+        }
+    };
+
+    if output.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("svg")) {
+        draw(SVGBackend::new(&output, SIZE).into_drawing_area(), &chart)?;
+    } else {
+        draw(BitMapBackend::new(&output, SIZE).into_drawing_area(), &chart)?;
+    }
+    println!("Wrote {}", Path::new(&output).display());
+    Ok(())
+}
@@ -0,0 +1,157 @@
+use crate::config;
+use clap::ValueEnum;
+use csv::WriterBuilder;
+use eyre::eyre;
+use serde::Serialize;
+use solana_pubkey::Pubkey;
+use solfi_sim::Simulator;
+use solfi_sim::simulator::to_atomic;
+use solfi_sim::swap::SwapDirection;
+use std::fmt;
+use std::io::stdout;
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum Perturbation {
+    /// Multiply the field by --factor
+    Scale,
+    /// Set the field to zero
+    Zero,
+    /// Set every byte of the field to 0xff
+    Max,
+}
+
+impl fmt::Display for Perturbation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Perturbation::Scale => write!(f, "scale"),
+            Perturbation::Zero => write!(f, "zero"),
+            Perturbation::Max => write!(f, "max"),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct FieldSensitivity {
+    offset: usize,
+    width: usize,
+    original: u64,
+    perturbed: u64,
+    out_amount: Option<f64>,
+    change_bps: Option<f64>,
+    error: Option<String>,
+    error_kind: Option<String>,
+}
+
+fn read_field(data: &[u8], offset: usize, width: usize) -> u64 {
+    let mut bytes = [0u8; 8];
+    bytes[..width].copy_from_slice(&data[offset..offset + width]);
+    u64::from_le_bytes(bytes)
+}
+
+fn write_field(data: &mut [u8], offset: usize, width: usize, value: u64) {
+    data[offset..offset + width].copy_from_slice(&value.to_le_bytes()[..width]);
+}
+
+fn perturb(value: u64, width: usize, perturbation: Perturbation, factor: f64) -> u64 {
+    let max = if width == 8 { u64::MAX } else { (1u64 << (width * 8)) - 1 };
+    match perturbation {
+        Perturbation::Scale => (value as f64 * factor).round().clamp(0.0, max as f64) as u64,
+        Perturbation::Zero => 0,
+        Perturbation::Max => max,
+    }
+}
+
+/// How [`sensitivity`] perturbs the market account and how much of the ranking it prints.
+pub struct SensitivityOptions {
+    pub perturbation: Perturbation,
+    /// Multiplier for [`Perturbation::Scale`]
+    pub factor: f64,
+    /// Field width in bytes: 1, 2, 4 or 8
+    pub width: usize,
+    /// First byte to perturb
+    pub start: usize,
+    /// End of the perturbed range, the whole account when unset
+    pub end: Option<usize>,
+    /// Number of fields to print
+    pub top: usize,
+}
+
+/// Perturbs each aligned `width`-byte field of `market`'s account data in `[start, end)` one at a
+/// time, replays the same swap against every variant, and prints the fields ranked by how far the
+/// output moved. Fields that make the swap fail are ranked first.
+pub fn sensitivity(
+    market: Pubkey,
+    direction: SwapDirection,
+    amount: f64,
+    options: &SensitivityOptions,
+) -> eyre::Result<()> {
+    let SensitivityOptions { perturbation, factor, width, start, end, top } = *options;
+    if ![1, 2, 4, 8].contains(&width) {
+        eyre::bail!("field width must be 1, 2, 4 or 8 bytes");
+    }
+
+    let harness = Simulator::from_config(config(), None)?.harness(
+        market,
+        direction,
+        to_atomic(direction, amount),
+    )?;
+    let original = harness.market_data()?;
+    let baseline =
+        harness.run(Some(&original), None).map_err(|e| eyre!("baseline swap failed: {e}"))?;
+    let end = end.unwrap_or(original.len()).min(original.len());
+    tracing::info!(
+        "baseline: {amount} {direction} on {market} -> {baseline}, perturbing bytes {start}..{end} \
+         ({perturbation}, {width}-byte fields)"
+    );
+    if baseline <= 0.0 {
+        tracing::warn!("baseline swap paid out nothing, only failures can be ranked");
+    }
+
+    let mut results = Vec::new();
+    for offset in (start.next_multiple_of(width)..end).step_by(width) {
+        if offset + width > end {
+            break;
+        }
+        let value = read_field(&original, offset, width);
+        let perturbed = perturb(value, width, perturbation, factor);
+        if perturbed == value {
+            continue;
+        }
+        let mut data = original.clone();
+        write_field(&mut data, offset, width, perturbed);
+
+        let (out_amount, error) = match harness.run(Some(&data), None) {
+            Ok(out) => (Some(out), None),
+            Err(err) => (None, Some(err)),
+        };
+        results.push(FieldSensitivity {
+            offset,
+            width,
+            original: value,
+            perturbed,
+            out_amount,
+            change_bps: out_amount
+                .filter(|_| baseline > 0.0)
+                .map(|out| (out / baseline - 1.0) * 10_000.0),
+            error: error.as_ref().map(|e| e.to_string()),
+            error_kind: error.as_ref().map(|e| e.label()),
+        });
+    }
+
+    // Failures first, then by how much the output moved.
+    results.sort_by(|a, b| {
+        let key = |r: &FieldSensitivity| match r.error {
+            Some(_) => f64::INFINITY,
+            None => r.change_bps.map_or(0.0, f64::abs),
+        };
+        key(b).total_cmp(&key(a))
+    });
+
+    let mut wtr = WriterBuilder::new().from_writer(stdout());
+    for result in results.iter().take(top) {
+        wtr.serialize(result)?;
+    }
+    wtr.flush()?;
+
+    Ok(())
+}
@@ -1,153 +1,137 @@
-use crate::constants::{SOLFI_MARKETS, SOLFI_PROGRAM, USDC, WSOL};
-use crate::swap::{SwapDirection, create_swap_ix};
-use crate::types::{AccountWithAddress, FetchMetadata};
-use crate::utils::token_balance;
+use crate::config;
 use csv::WriterBuilder;
-use eyre::eyre;
-use litesvm::LiteSVM;
-use solana_account::Account;
-use solana_keypair::Keypair;
-use solana_pubkey::Pubkey;
-use solana_sdk::native_token::sol_to_lamports;
-use solana_sdk::program_pack::Pack;
-use solana_sdk::rent::Rent;
-use solana_sdk::rent_collector::RENT_EXEMPT_RENT_EPOCH;
-use solana_signer::Signer;
-use solana_system_interface::instruction::transfer;
-use solana_transaction::Transaction;
-use spl_associated_token_account::get_associated_token_address;
-use spl_associated_token_account::instruction::create_associated_token_account_idempotent;
-use spl_token::instruction::sync_native;
-use spl_token::state::{Account as TokenAccount, AccountState};
+use solfi_sim::swap::SwapDirection;
+use solfi_sim::venue::load_venues;
+use solfi_sim::{ComputeBudgetOptions, ReferenceKind, ReferenceParams, Simulator, SwapResult};
 use std::io::stdout;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
-const SOLFI_PROGRAM_PATH: &str = "data/solfi.so";
 const DEFAULT_SWAP_AMOUNT_SOL: f64 = 10.0;
 const DEFAULT_SWAP_AMOUNT_USDC: f64 = 1000.0;
-const SOL_DECIMALS: i32 = 9;
-const USDC_DECIMALS: i32 = 6;
 
-fn mk_ata_account(mint: &Pubkey, user: &Pubkey, amount: u64) -> Account {
-    let ata = TokenAccount {
-        mint: *mint,
-        owner: *user,
-        amount,
-        state: AccountState::Initialized,
-        ..Default::default()
-    };
-    let mut data = vec![0u8; TokenAccount::LEN];
-    ata.pack_into_slice(&mut data);
-    Account {
-        lamports: Rent::default().minimum_balance(data.len()),
-        data,
-        owner: spl_token::id(),
-        executable: false,
-        rent_epoch: RENT_EXEMPT_RENT_EPOCH,
-    }
+pub fn simulate(
+    direction: SwapDirection,
+    amount: Option<f64>,
+    slot: Option<u64>,
+    ignore_errors: bool,
+    prn: bool,
+) -> eyre::Result<Vec<SwapResult>> {
+    simulate_in(&config().data_dir, direction, amount, slot, ignore_errors, prn)
 }
 
-pub fn simulate(
+/// Same as [`simulate`] but against the snapshot stored in `data_dir`.
+pub fn simulate_in(
+    data_dir: &Path,
     direction: SwapDirection,
     amount: Option<f64>,
     slot: Option<u64>,
     ignore_errors: bool,
     prn: bool,
 ) -> eyre::Result<Vec<SwapResult>> {
-    let user_keypair = Keypair::new();
-    let user = user_keypair.pubkey();
-    let mut svm =
-        LiteSVM::new().with_sysvars().with_precompiles().with_sigverify(true).with_spl_programs();
+    let options = SimulateOptions::default();
+    simulate_with(data_dir, direction, amount, slot, ignore_errors, prn, &options)
+}
 
-    for acct in AccountWithAddress::read_all()? {
-        svm.set_account(acct.address, acct.account)?;
-    }
-    svm.add_program_from_file(SOLFI_PROGRAM, SOLFI_PROGRAM_PATH)?;
-    if let Some(slot) = slot.or(FetchMetadata::read().map(|m| m.slot())) {
-        svm.warp_to_slot(slot);
-    }
+/// What the `simulate` command adds on top of the plain simulation the other commands use.
+#[derive(Default)]
+pub struct SimulateOptions {
+    /// Compute budget instructions to put in front of each swap
+    pub budget: ComputeBudgetOptions,
+    /// Search each market for the smallest compute unit limit that works and use it, so the
+    /// priority fee is charged on that
+    pub min_compute_units: bool,
+    /// JSON file of other venues to quote alongside SolFi
+    pub venues: Option<PathBuf>,
+    /// Reference models to quote on every SolFi market's vault balances
+    pub references: Vec<ReferenceKind>,
+    pub reference_params: ReferenceParams,
+    /// Print every [`SwapResultRow`] column under a header instead of the plain four
+    pub wide: bool,
+}
 
-    let (to_mint, from_decimals, to_decimals, in_amount_ui) = match direction {
-        SwapDirection::SolToUsdc => {
-            (&USDC, SOL_DECIMALS, USDC_DECIMALS, amount.unwrap_or(DEFAULT_SWAP_AMOUNT_SOL))
+/// Same as [`simulate_in`] with [`SimulateOptions`].
+pub fn simulate_with(
+    data_dir: &Path,
+    direction: SwapDirection,
+    amount: Option<f64>,
+    slot: Option<u64>,
+    ignore_errors: bool,
+    prn: bool,
+    options: &SimulateOptions,
+) -> eyre::Result<Vec<SwapResult>> {
+    let in_amount_ui = amount.unwrap_or(match direction {
+        SwapDirection::SolToUsdc => DEFAULT_SWAP_AMOUNT_SOL,
+        SwapDirection::UsdcToSol => DEFAULT_SWAP_AMOUNT_USDC,
+    });
+
+    let budget = options.budget;
+    let mut simulator = Simulator::load_with(data_dir, slot, config().solfi())?;
+    if let Some(path) = &options.venues {
+        for venue in load_venues(path)? {
+            simulator.add_venue(Arc::new(venue))?;
         }
-        SwapDirection::UsdcToSol => {
-            (&WSOL, USDC_DECIMALS, SOL_DECIMALS, amount.unwrap_or(DEFAULT_SWAP_AMOUNT_USDC))
+    }
+    let mut results = if options.min_compute_units {
+        let mut results = vec![];
+        for (venue, market) in simulator.markets() {
+            let limit = simulator.min_compute_unit_limit(
+                venue,
+                &market,
+                direction,
+                in_amount_ui,
+                budget.unit_price,
+            )?;
+            let budget = ComputeBudgetOptions { unit_limit: limit.or(budget.unit_limit), ..budget };
+            results.push(simulator.simulate_on(
+                venue,
+                &market,
+                direction,
+                in_amount_ui,
+                &budget,
+            )?);
         }
+        results
+    } else {
+        simulator.quote_all_with(direction, in_amount_ui, &budget)?
     };
-
-    let amount_in_atomic = (in_amount_ui * 10f64.powi(from_decimals)) as u64;
-    let total_amount_needed = amount_in_atomic * SOLFI_MARKETS.len() as u64;
-
-    let fee_lamports = sol_to_lamports(1.0);
-    match direction {
-        SwapDirection::SolToUsdc => {
-            let airdrop_amount = total_amount_needed + fee_lamports;
-            svm.airdrop(&user, airdrop_amount)
-                .map_err(|e| eyre!("failed to airdrop SOL: {}", e.err))?;
-        }
-        SwapDirection::UsdcToSol => {
-            svm.airdrop(&user, fee_lamports)
-                .map_err(|e| eyre!("failed to airdrop SOL: {}", e.err))?;
-            let usdc_ata = get_associated_token_address(&user, &USDC);
-            let usdc_account = mk_ata_account(&USDC, &user, total_amount_needed);
-            svm.set_account(usdc_ata, usdc_account)?;
+    for market in config().markets() {
+        for kind in &options.references {
+            let model = simulator.reference_model(&market, *kind, options.reference_params)?;
+            let out_amount = model.quote(direction, in_amount_ui);
+            results.push(SwapResult {
+                venue: kind.to_string(),
+                market: market.to_string(),
+                in_amount: in_amount_ui,
+                out_amount,
+                error: None,
+                costs: None,
+                net_price: None,
+                compute_units: None,
+                compute_unit_limit: None,
+            });
         }
     }
+    if ignore_errors {
+        results.retain(|r| r.error.is_none());
+    }
 
-    let wsol_ata = get_associated_token_address(&user, &WSOL);
-    let mut wtr = WriterBuilder::new().has_headers(false).from_writer(stdout());
-    let mut results = vec![];
-
-    for market in SOLFI_MARKETS {
-        let to_ata = get_associated_token_address(&user, to_mint);
-        let balance_before = token_balance(&svm, &to_ata);
-
-        let mut instructions = vec![
-            create_associated_token_account_idempotent(&user, &user, &WSOL, &spl_token::id()),
-            create_associated_token_account_idempotent(&user, &user, &USDC, &spl_token::id()),
-        ];
-
-        if direction == SwapDirection::SolToUsdc {
-            instructions.extend([
-                transfer(&user, &wsol_ata, amount_in_atomic),
-                sync_native(&spl_token::id(), &wsol_ata)?,
-            ]);
-        }
-
-        instructions.push(create_swap_ix(direction, market, &user, &WSOL, &USDC, amount_in_atomic));
-
-        let tx = Transaction::new_with_payer(&instructions, Some(&user));
-        let signed_tx = Transaction::new(&[&user_keypair], tx.message, svm.latest_blockhash());
-
-        match svm.send_transaction(signed_tx) {
-            Ok(_) => {
-                let balance_after = token_balance(&svm, &to_ata);
-                let out_amount_atomic = balance_after - balance_before;
-                let out_amount_ui = out_amount_atomic as f64 / 10f64.powi(to_decimals);
-                let swap_result = SwapResult {
-                    market: market.to_string(),
-                    in_amount: in_amount_ui,
-                    out_amount: Some(out_amount_ui),
-                    error: None,
-                };
-                if prn {
-                    wtr.serialize(&swap_result)?;
-                }
-                results.push(swap_result);
-            }
-            Err(err) => {
-                if !ignore_errors {
-                    let swap_result = SwapResult {
-                        market: market.to_string(),
-                        in_amount: in_amount_ui,
-                        out_amount: None,
-                        error: Some(err.err.to_string()),
-                    };
-                    if prn {
-                        wtr.serialize(&swap_result)?;
-                    }
-                    results.push(swap_result);
-                }
+    if prn {
+        // Other venues and reference models share SolFi's market addresses, so they need the
+        // venue column to tell them apart, and compute budget options only show in the compute
+        // columns.
+        let budget_set = budget.unit_limit.is_some() || budget.unit_price.is_some();
+        let wide = options.wide
+            || options.venues.is_some()
+            || !options.references.is_empty()
+            || budget_set
+            || options.min_compute_units;
+        let mut wtr = WriterBuilder::new().has_headers(wide).from_writer(stdout());
+        for result in &results {
+            if wide {
+                wtr.serialize(SwapResultRow::from(result))?;
+            } else {
+                wtr.serialize(PlainRow::from(result))?;
             }
         }
         wtr.flush()?;
@@ -156,10 +140,61 @@ pub fn simulate(
     Ok(results)
 }
 
+/// The original header-less `simulate` row: market, amount in, amount out and error.
 #[derive(serde::Serialize)]
-pub struct SwapResult {
-    pub market: String,
-    pub in_amount: f64,
-    pub out_amount: Option<f64>,
-    pub error: Option<String>,
+struct PlainRow<'a> {
+    market: &'a str,
+    in_amount: f64,
+    out_amount: Option<f64>,
+    error: Option<String>,
+}
+
+impl<'a> From<&'a SwapResult> for PlainRow<'a> {
+    fn from(result: &'a SwapResult) -> Self {
+        Self {
+            market: &result.market,
+            in_amount: result.in_amount,
+            out_amount: result.out_amount,
+            error: result.error.as_ref().map(|e| e.to_string()),
+        }
+    }
+}
+
+/// [`SwapResult`] flattened for CSV, with the error as its message plus a groupable label and
+/// the costs in lamports.
+#[derive(serde::Serialize)]
+struct SwapResultRow<'a> {
+    market: &'a str,
+    in_amount: f64,
+    out_amount: Option<f64>,
+    error: Option<String>,
+    error_kind: Option<String>,
+    network_fee: Option<u64>,
+    ata_rent: Option<u64>,
+    wsol_rent: Option<u64>,
+    net_price: Option<f64>,
+    compute_units: Option<u64>,
+    compute_unit_limit: Option<u32>,
+    priority_fee: Option<u64>,
+    venue: &'a str,
+}
+
+impl<'a> From<&'a SwapResult> for SwapResultRow<'a> {
+    fn from(result: &'a SwapResult) -> Self {
+        Self {
+            market: &result.market,
+            in_amount: result.in_amount,
+            out_amount: result.out_amount,
+            error: result.error.as_ref().map(|e| e.to_string()),
+            error_kind: result.error.as_ref().map(|e| e.label()),
+            network_fee: result.costs.map(|c| c.network_fee),
+            ata_rent: result.costs.map(|c| c.ata_rent),
+            wsol_rent: result.costs.map(|c| c.wsol_rent),
+            net_price: result.net_price,
+            compute_units: result.compute_units,
+            compute_unit_limit: result.compute_unit_limit,
+            priority_fee: result.costs.map(|c| c.priority_fee),
+            venue: &result.venue,
+        }
+    }
 }
@@ -0,0 +1,179 @@
+use crate::config;
+use crate::trace::{Access, memory_accesses, trace_instruction};
+use eyre::eyre;
+use litesvm::types::{FailedTransactionMetadata, TransactionMetadata};
+use serde::Serialize;
+use solana_keypair::Keypair;
+use solana_message::{Message, SanitizedMessage};
+use solana_pubkey::Pubkey;
+use solana_sdk::transaction::TransactionError;
+use solana_signer::Signer;
+use solana_transaction::Transaction;
+use solfi_sim::simulator::{fund_user, load_snapshot_svm, swap_instructions, to_atomic};
+use solfi_sim::swap::{SWAP_ACCOUNT_ROLES, SwapDirection};
+use solfi_sim::types::SimError;
+use std::collections::HashSet;
+use std::fs;
+
+#[derive(Serialize)]
+struct ByteRange {
+    start: usize,
+    end: usize,
+}
+
+#[derive(Serialize)]
+struct AccountAccess {
+    role: String,
+    address: String,
+    data_len: usize,
+    bytes_read: usize,
+    bytes_written: usize,
+    /// Byte ranges the program loaded from. Reads made inside syscalls like sol_memcpy_ are missing
+    reads: Vec<ByteRange>,
+    /// Byte ranges the program stored to directly
+    writes: Vec<ByteRange>,
+    /// Byte ranges that differ after the transaction, including writes made through CPI
+    changed: Vec<ByteRange>,
+}
+
+#[derive(Serialize)]
+struct TraceReport {
+    market: String,
+    direction: SwapDirection,
+    amount: f64,
+    instructions_executed: usize,
+    error: Option<SimError>,
+    accounts: Vec<AccountAccess>,
+}
+
+fn ranges(mask: &[bool]) -> Vec<ByteRange> {
+    let mut ranges = Vec::new();
+    let mut start = None;
+    for (i, set) in mask.iter().chain([&false]).enumerate() {
+        match (start, set) {
+            (None, true) => start = Some(i),
+            (Some(s), false) => {
+                ranges.push(ByteRange { start: s, end: i });
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    ranges
+}
+
+/// Runs a single swap on `market` with SBF instruction tracing on and reports which bytes of each
+/// account's data the SolFi program loaded and stored.
+pub fn trace_swap(
+    market: Pubkey,
+    direction: SwapDirection,
+    amount: f64,
+    json: bool,
+) -> eyre::Result<()> {
+    let mut svm = load_snapshot_svm(&config().data_dir, None)?;
+    let user_keypair = Keypair::new();
+    let user = user_keypair.pubkey();
+    let amount_in_atomic = to_atomic(direction, amount);
+    fund_user(&mut svm, &user, direction, amount_in_atomic)?;
+
+    // The token account setup runs in LiteSVM, the swap itself on the traced program.
+    let mut instructions = swap_instructions(direction, &market, &user, amount_in_atomic)?;
+    let swap_ix = instructions.pop().unwrap();
+    let tx = Transaction::new_with_payer(&instructions, Some(&user));
+    let signed_tx = Transaction::new(&[&user_keypair], tx.message, svm.latest_blockhash());
+    svm.send_transaction(signed_tx)
+        .map_err(|e| eyre!("failed to set up the user's token accounts: {}", e.err))?;
+    let swap_index = instructions.len();
+    instructions.push(swap_ix.clone());
+    let message = SanitizedMessage::try_from_legacy_message(
+        Message::new(&instructions, Some(&user)),
+        &HashSet::new(),
+    )?;
+    let program = fs::read(&config().program_path)?;
+    let traced = trace_instruction(&svm, &message, swap_index, &program)?;
+    let error = traced.result.err().map(|err| {
+        let failed = FailedTransactionMetadata {
+            err: TransactionError::InstructionError(swap_index as u8, err),
+            meta: TransactionMetadata { logs: traced.logs, ..Default::default() },
+        };
+        SimError::from_failed_tx(&failed, &instructions)
+    });
+
+    let run = traced.run.ok_or_else(|| eyre!("the SolFi program was never invoked"))?;
+    let accesses = memory_accesses(&run.trace);
+
+    let mut accounts = Vec::new();
+    for (i, (address, vm_data_addr, data_len)) in run.accounts.iter().enumerate() {
+        if *data_len == 0 {
+            continue;
+        }
+        let mut read = vec![false; *data_len];
+        let mut written = vec![false; *data_len];
+        for access in &accesses {
+            let end = vm_data_addr + *data_len as u64;
+            if access.vm_addr < *vm_data_addr || access.vm_addr >= end {
+                continue;
+            }
+            let start = (access.vm_addr - vm_data_addr) as usize;
+            let stop = (start + access.len as usize).min(*data_len);
+            let mask = if access.access == Access::Read { &mut read } else { &mut written };
+            mask[start..stop].iter_mut().for_each(|b| *b = true);
+        }
+
+        let post = traced.post_data.get(address).cloned().unwrap_or_default();
+        let pre = traced.pre_data.get(address).cloned().unwrap_or_default();
+        let changed: Vec<bool> =
+            (0..*data_len.max(&post.len())).map(|b| pre.get(b) != post.get(b)).collect();
+
+        accounts.push(AccountAccess {
+            role: SWAP_ACCOUNT_ROLES.get(i).unwrap_or(&"unknown").to_string(),
+            address: address.to_string(),
+            data_len: *data_len,
+            bytes_read: read.iter().filter(|b| **b).count(),
+            bytes_written: written.iter().filter(|b| **b).count(),
+            reads: ranges(&read),
+            writes: ranges(&written),
+            changed: ranges(&changed),
+        });
+    }
+
+    let report = TraceReport {
+        market: market.to_string(),
+        direction,
+        amount,
+        instructions_executed: run.trace.len(),
+        error,
+        accounts,
+    };
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+
+    println!(
+        "== traced {} {} on {} ({} SBF instructions) ==",
+        report.amount, report.direction, report.market, report.instructions_executed
+    );
+    if let Some(error) = &report.error {
+        println!("swap failed: {error}");
+    }
+    let fmt_ranges = |ranges: &[ByteRange]| {
+        ranges.iter().map(|r| format!("{}..{}", r.start, r.end)).collect::<Vec<_>>().join(" ")
+    };
+    for account in &report.accounts {
+        println!(
+            "\n--- {} {} ({} bytes): read {}, wrote {} ---",
+            account.role,
+            account.address,
+            account.data_len,
+            account.bytes_read,
+            account.bytes_written
+        );
+        println!("  reads:   {}", fmt_ranges(&account.reads));
+        println!("  writes:  {}", fmt_ranges(&account.writes));
+        println!("  changed: {}", fmt_ranges(&account.changed));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,146 @@
+use crate::cmd::read_solfi_instructions;
+use crate::config;
+use csv::WriterBuilder;
+use eyre::eyre;
+use serde::Serialize;
+use solana_pubkey::Pubkey;
+use solfi_sim::SwapHarness;
+use solfi_sim::swap::SwapDirection;
+use solfi_sim::types::FetchMetadata;
+use std::collections::HashMap;
+use std::io::stdout;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+#[derive(Serialize)]
+struct ValidationRow {
+    file: String,
+    signature: Option<String>,
+    slot: u64,
+    snapshot_slot: Option<u64>,
+    instruction: usize,
+    inner_index: Option<usize>,
+    market: String,
+    direction: Option<SwapDirection>,
+    amount_in: u64,
+    actual_out: Option<f64>,
+    simulated_out: Option<f64>,
+    diff: Option<f64>,
+    diff_bps: Option<f64>,
+    note: Option<String>,
+}
+
+/// Replays every SolFi swap in the saved transactions `files` against the snapshot in
+/// `snapshot` and compares the simulated output with what the market's output vault actually
+/// paid out on chain. Prints one CSV row per swap and a summary on stderr.
+pub fn validate(files: &[PathBuf], snapshot: &Path) -> eyre::Result<()> {
+    let solfi = config().solfi();
+    let snapshot_slot = FetchMetadata::read_from(snapshot).map(|m| m.slot());
+    let mut wtr = WriterBuilder::new().from_writer(stdout());
+    let mut diffs_bps = Vec::new();
+    let mut rows = 0;
+
+    for file in files {
+        let decoded = read_solfi_instructions(file, &solfi)?;
+        if snapshot_slot.is_some_and(|slot| slot >= decoded.slot) {
+            tracing::warn!("Snapshot is not older than {} (slot {})", file.display(), decoded.slot);
+        }
+        let swaps: Vec<_> = decoded.instructions.iter().filter(|ix| ix.swap.is_some()).collect();
+        let mut swaps_per_market: HashMap<&str, usize> = HashMap::new();
+        for ix in &swaps {
+            if let Some(market) = ix.accounts.get(1) {
+                *swaps_per_market.entry(&market.address).or_default() += 1;
+            }
+        }
+
+        for ix in swaps {
+            let swap = ix.swap.as_ref().unwrap();
+            let market = ix.accounts.get(1).map(|a| a.address.clone()).unwrap_or_default();
+            let mut row = ValidationRow {
+                file: file.display().to_string(),
+                signature: decoded.signature.clone(),
+                slot: decoded.slot,
+                snapshot_slot,
+                instruction: ix.instruction,
+                inner_index: ix.inner_index,
+                market: market.clone(),
+                direction: swap.direction,
+                amount_in: swap.amount_in,
+                actual_out: None,
+                simulated_out: None,
+                diff: None,
+                diff_bps: None,
+                note: None,
+            };
+            rows += 1;
+
+            // The output vault is the one that pays out: USDC (B) when selling SOL, SOL (A) when
+            // buying it.
+            let output_role = match swap.direction {
+                Some(SwapDirection::SolToUsdc) => "market vault B",
+                Some(SwapDirection::UsdcToSol) => "market vault A",
+                None => {
+                    row.note = Some(format!("unknown direction byte {}", swap.direction_byte));
+                    wtr.serialize(row)?;
+                    continue;
+                }
+            };
+            if let Some(error) = &decoded.error {
+                row.note = Some(format!("failed on chain: {error}"));
+                wtr.serialize(row)?;
+                continue;
+            }
+            // Vault balances are only known for the whole transaction.
+            if swaps_per_market[market.as_str()] == 1 {
+                row.actual_out = ix
+                    .vault_changes
+                    .iter()
+                    .find(|c| c.role == output_role)
+                    .map(|c| c.to_ui(-c.change));
+            } else {
+                row.note =
+                    Some("several swaps on this market, on-chain output is ambiguous".into());
+            }
+
+            let market = Pubkey::from_str(&market).map_err(|e| eyre!("invalid market: {e}"))?;
+            let direction = swap.direction.unwrap();
+            let harness = SwapHarness::new_in(
+                snapshot,
+                Some(decoded.slot),
+                solfi.clone(),
+                market,
+                direction,
+                swap.amount_in,
+            )?;
+            match harness.run(None, None) {
+                Ok(out) => row.simulated_out = Some(out),
+                Err(err) => row.note = Some(err.to_string()),
+            }
+
+            if let (Some(actual), Some(simulated)) = (row.actual_out, row.simulated_out) {
+                row.diff = Some(simulated - actual);
+                if actual > 0.0 {
+                    let bps = (simulated / actual - 1.0) * 10_000.0;
+                    row.diff_bps = Some(bps);
+                    diffs_bps.push(bps);
+                }
+            }
+            wtr.serialize(row)?;
+        }
+    }
+    wtr.flush()?;
+
+    let compared = diffs_bps.len();
+    if compared == 0 {
+        tracing::warn!("No swaps could be compared ({rows} found)");
+        return Ok(());
+    }
+    let exact = diffs_bps.iter().filter(|bps| **bps == 0.0).count();
+    let mean_abs = diffs_bps.iter().map(|bps| bps.abs()).sum::<f64>() / compared as f64;
+    let max_abs = diffs_bps.iter().fold(0.0f64, |max, bps| max.max(bps.abs()));
+    tracing::info!(
+        "Compared {compared} of {rows} swaps: {exact} exact, mean |diff| {mean_abs:.3} bps, max |diff| {max_abs:.3} bps"
+    );
+
+    Ok(())
+}
@@ -0,0 +1,181 @@
+use crate::constants::{DATA_DIR, DEFAULT_RPC_URL, SOLFI_MARKETS, SOLFI_PROGRAM_PATH, USDC, WSOL};
+use crate::error::{Error, Result};
+use crate::venue::Solfi;
+use serde::{Deserialize, Deserializer};
+use solana_pubkey::Pubkey;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Config file read from the working directory when `--config` isn't given.
+pub const CONFIG_FILE: &str = "solfi-sim.toml";
+
+/// Settings read from `solfi-sim.toml`. Anything left out keeps the built-in default from
+/// [`constants`](crate::constants).
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Where snapshots are fetched to and simulated from
+    pub data_dir: PathBuf,
+    /// The SolFi program binary
+    pub program_path: PathBuf,
+    /// RPC endpoints, most preferred first
+    pub rpc_urls: Vec<String>,
+    pub pairs: Vec<PairConfig>,
+    pub service: ServiceConfig,
+}
+
+/// A token pair and the SolFi markets trading it.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PairConfig {
+    #[serde(deserialize_with = "deserialize_pubkey")]
+    pub base_mint: Pubkey,
+    #[serde(deserialize_with = "deserialize_pubkey")]
+    pub quote_mint: Pubkey,
+    #[serde(deserialize_with = "deserialize_pubkeys")]
+    pub markets: Vec<Pubkey>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServiceConfig {
+    pub port: u16,
+    /// How often the service refetches the snapshot
+    pub fetch_interval_ms: u64,
+    /// SOL sizes `get_prices` quotes
+    pub ladder: Vec<f64>,
+    /// Follow the accounts over the PubSub API instead of polling
+    pub subscribe: bool,
+    /// PubSub endpoint (default: the first RPC endpoint with a ws:// or wss:// scheme)
+    pub ws_url: Option<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            data_dir: DATA_DIR.into(),
+            program_path: SOLFI_PROGRAM_PATH.into(),
+            rpc_urls: vec![DEFAULT_RPC_URL.to_string()],
+            pairs: vec![PairConfig {
+                base_mint: WSOL,
+                quote_mint: USDC,
+                markets: SOLFI_MARKETS.to_vec(),
+            }],
+            service: ServiceConfig::default(),
+        }
+    }
+}
+
+impl Default for ServiceConfig {
+    fn default() -> Self {
+        Self {
+            port: 8080,
+            fetch_interval_ms: 400,
+            ladder: vec![1.0, 10.0, 100.0],
+            subscribe: false,
+            ws_url: None,
+        }
+    }
+}
+
+fn deserialize_pubkey<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Pubkey, D::Error> {
+    Pubkey::from_str(&String::deserialize(deserializer)?).map_err(serde::de::Error::custom)
+}
+
+fn deserialize_pubkeys<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Vec<Pubkey>, D::Error> {
+    Vec::<String>::deserialize(deserializer)?
+        .iter()
+        .map(|key| Pubkey::from_str(key).map_err(serde::de::Error::custom))
+        .collect()
+}
+
+impl Config {
+    /// Reads and validates the config file at `path`.
+    pub fn load(path: &Path) -> Result<Self> {
+        let config: Config = toml::from_str(&fs::read_to_string(path)?)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Reads [`CONFIG_FILE`] from the working directory, or the defaults if there isn't one.
+    pub fn discover() -> Result<Self> {
+        let path = Path::new(CONFIG_FILE);
+        if path.exists() { Self::load(path) } else { Ok(Self::default()) }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        let invalid = |reason: String| Err(Error::InvalidInput(reason));
+        if self.rpc_urls.is_empty() {
+            return invalid("no rpc_urls".into());
+        }
+        if let Some(url) =
+            self.rpc_urls.iter().find(|u| !u.starts_with("http://") && !u.starts_with("https://"))
+        {
+            return invalid(format!("rpc url {url} isn't http(s)"));
+        }
+        if self.pairs.is_empty() {
+            return invalid("no pairs".into());
+        }
+        // The swap instruction and the decimals are WSOL/USDC's.
+        if let Some(pair) = self.pairs.iter().find(|p| p.base_mint != WSOL || p.quote_mint != USDC)
+        {
+            return invalid(format!(
+                "unsupported pair {}/{}, only WSOL/USDC can be simulated",
+                pair.base_mint, pair.quote_mint
+            ));
+        }
+        let mut seen = HashSet::new();
+        for pair in &self.pairs {
+            if pair.markets.is_empty() {
+                return invalid("a pair has no markets".into());
+            }
+            if let Some(market) = pair.markets.iter().find(|m| !seen.insert(**m)) {
+                return invalid(format!("market {market} is listed twice"));
+            }
+        }
+        if let Some(url) = self
+            .service
+            .ws_url
+            .as_ref()
+            .filter(|u| !u.starts_with("ws://") && !u.starts_with("wss://"))
+        {
+            return invalid(format!("service.ws_url {url} isn't ws(s)"));
+        }
+        if self.service.fetch_interval_ms == 0 {
+            return invalid("service.fetch_interval_ms must be positive".into());
+        }
+        if self.service.ladder.is_empty() {
+            return invalid("service.ladder is empty".into());
+        }
+        if let Some(size) = self.service.ladder.iter().find(|s| !(s.is_finite() && **s > 0.0)) {
+            return invalid(format!("service.ladder size {size} isn't a positive amount"));
+        }
+        Ok(())
+    }
+
+    /// Every configured market, in file order.
+    pub fn markets(&self) -> Vec<Pubkey> {
+        self.pairs.iter().flat_map(|pair| pair.markets.iter().copied()).collect()
+    }
+
+    /// Archived snapshots live under the data directory.
+    pub fn snapshots_dir(&self) -> PathBuf {
+        self.data_dir.join("snapshots")
+    }
+
+    /// Where `fit` saves its curve model and `approx-quote` reads it by default.
+    pub fn curve_model_path(&self) -> PathBuf {
+        self.data_dir.join("curve_model.json")
+    }
+
+    /// The SolFi venue with the configured markets and program.
+    pub fn solfi(&self) -> Solfi {
+        Solfi::new(self.markets(), self.program_path.clone())
+    }
+}
@@ -2,6 +2,13 @@ use solana_pubkey::{Pubkey, pubkey};
 
 pub const DEFAULT_RPC_URL: &str = "https://api.mainnet-beta.solana.com";
 
+pub const DATA_DIR: &str = "data";
+pub const SLOT_DURATION_MS: u64 = 400;
+pub const SOLFI_PROGRAM_PATH: &str = "data/solfi.so";
+
+pub const SOL_DECIMALS: i32 = 9;
+pub const USDC_DECIMALS: i32 = 6;
+
 pub const SOLFI_PROGRAM: Pubkey = pubkey!("SoLFiHG9TfgtdUXUjWAxi3LtvYuFyDLVhBWxdMZxyCe");
 pub const WSOL: Pubkey = pubkey!("So11111111111111111111111111111111111111112");
 pub const USDC: Pubkey = pubkey!("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v");
@@ -0,0 +1,404 @@
+use crate::config;
+use crate::service::{fetch_state_task, subscribe_state_task};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Paragraph, Row, Table};
+use ratatui::{DefaultTerminal, Frame};
+use solana_pubkey::Pubkey;
+use solfi_sim::Simulator;
+use solfi_sim::rpc::RpcPool;
+use solfi_sim::swap::SwapDirection;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::{UnboundedReceiver, unbounded_channel};
+use tokio::time::interval;
+
+/// Ladders `l` cycles through after the configured one, in SOL.
+const LADDERS: [&[f64]; 3] = [&[0.1, 1.0, 10.0], &[1.0, 10.0, 100.0], &[10.0, 100.0, 1_000.0]];
+
+/// Redraw this often even without updates, so ages keep counting.
+const REDRAW_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Markets this close to their cutoff slot are highlighted.
+const CUTOFF_WARNING_SLOTS: i64 = 20;
+
+enum Update {
+    Fetched(eyre::Result<u64>),
+    Key(KeyCode),
+}
+
+/// Bid and ask of one market at one ladder size.
+struct QuoteRow {
+    market: Pubkey,
+    size: f64,
+    /// USDC per SOL selling `size` SOL
+    bid: Option<f64>,
+    /// USDC per SOL spending `size` SOL worth of USDC at the best bid of the smallest size
+    ask: Option<f64>,
+}
+
+impl QuoteRow {
+    fn spread_bps(&self) -> Option<f64> {
+        let (bid, ask) = (self.bid?, self.ask?);
+        Some((ask - bid) / ((ask + bid) / 2.0) * 10_000.0)
+    }
+}
+
+/// Quotes on one snapshot.
+struct Quotes {
+    slot: u64,
+    loaded_at: Instant,
+    cutoffs: Vec<(Pubkey, Option<u64>)>,
+    rows: Vec<QuoteRow>,
+}
+
+struct Dashboard {
+    pool: Arc<RpcPool>,
+    direction: SwapDirection,
+    ladders: Vec<Vec<f64>>,
+    ladder: usize,
+    frozen: bool,
+    simulator: Option<Simulator>,
+    quotes: Option<Quotes>,
+    /// Why the last snapshot couldn't be loaded or quoted
+    quote_error: Option<String>,
+    latest_slot: Option<u64>,
+    fetches: u64,
+    failed_fetches: u64,
+    last_fetch: Option<Instant>,
+    last_fetch_error: Option<String>,
+}
+
+fn format_price(price: Option<f64>) -> String {
+    price.map_or("-".to_string(), |p| format!("{p:.4}"))
+}
+
+fn format_ago(at: Option<Instant>) -> String {
+    at.map_or("never".to_string(), |at| format!("{:.1}s ago", at.elapsed().as_secs_f64()))
+}
+
+impl Dashboard {
+    fn new(pool: Arc<RpcPool>) -> Self {
+        let configured = config().service.ladder.clone();
+        let others = LADDERS.iter().map(|l| l.to_vec()).filter(|l| *l != configured);
+        let ladders = std::iter::once(configured.clone()).chain(others).collect();
+        Self {
+            pool,
+            direction: SwapDirection::SolToUsdc,
+            ladders,
+            ladder: 0,
+            frozen: false,
+            simulator: None,
+            quotes: None,
+            quote_error: None,
+            latest_slot: None,
+            fetches: 0,
+            failed_fetches: 0,
+            last_fetch: None,
+            last_fetch_error: None,
+        }
+    }
+
+    /// Loads the snapshot the fetch loop last saved and quotes it.
+    fn load(&mut self) {
+        match Simulator::from_config(config(), None) {
+            Ok(simulator) => {
+                // The fetch loop may have saved a newer snapshot than it has reported yet
+                self.latest_slot = self.latest_slot.max(Some(simulator.slot()));
+                self.simulator = Some(simulator);
+                self.quote();
+            }
+            Err(e) => self.quote_error = Some(format!("failed to load snapshot: {e}")),
+        }
+    }
+
+    /// Quotes the loaded snapshot at the current ladder.
+    fn quote(&mut self) {
+        let Some(simulator) = &self.simulator else {
+            return;
+        };
+        let ladder = &self.ladders[self.ladder];
+        let markets = config().markets();
+        let quote = |market, direction, amount| {
+            simulator.quote(market, direction, amount).ok().filter(|out| *out > 0.0)
+        };
+
+        let bids: Vec<Vec<Option<f64>>> = ladder
+            .iter()
+            .map(|&size| {
+                markets
+                    .iter()
+                    .map(|market| Some(quote(market, SwapDirection::SolToUsdc, size)? / size))
+                    .collect()
+            })
+            .collect();
+        // Buys are sized in USDC, so aim for each SOL size at the best bid
+        let reference =
+            bids.first().and_then(|bids| bids.iter().flatten().copied().reduce(f64::max));
+        let mut rows = vec![];
+        for (&size, bids) in ladder.iter().zip(bids) {
+            for (market, bid) in markets.iter().zip(bids) {
+                let ask = reference.and_then(|reference| {
+                    let usdc = size * reference;
+                    Some(usdc / quote(market, SwapDirection::UsdcToSol, usdc)?)
+                });
+                rows.push(QuoteRow { market: *market, size, bid, ask });
+            }
+        }
+
+        let cutoffs = markets
+            .iter()
+            .map(|market| (*market, simulator.market_state(market).ok().map(|s| s.cutoff_slot)))
+            .collect();
+        self.quotes =
+            Some(Quotes { slot: simulator.slot(), loaded_at: Instant::now(), cutoffs, rows });
+        self.quote_error = None;
+    }
+
+    /// Applies an update. Returns whether the dashboard should quit, reload the snapshot or
+    /// requote the loaded one.
+    fn apply(&mut self, update: Update, reload: &mut bool, requote: &mut bool) -> bool {
+        match update {
+            Update::Fetched(Ok(slot)) => {
+                self.fetches += 1;
+                self.last_fetch = Some(Instant::now());
+                self.last_fetch_error = None;
+                self.latest_slot = Some(slot);
+                *reload |= !self.frozen && self.quotes.as_ref().is_none_or(|q| q.slot != slot);
+            }
+            Update::Fetched(Err(e)) => {
+                self.failed_fetches += 1;
+                self.last_fetch_error = Some(e.to_string());
+            }
+            Update::Key(KeyCode::Char('q') | KeyCode::Esc) => return true,
+            Update::Key(KeyCode::Char('d')) => self.direction = self.direction.opposite(),
+            Update::Key(KeyCode::Char('l')) => {
+                self.ladder = (self.ladder + 1) % self.ladders.len();
+                *requote = true;
+            }
+            Update::Key(KeyCode::Char(' ') | KeyCode::Char('f')) => {
+                self.frozen = !self.frozen;
+                *reload |= !self.frozen;
+            }
+            Update::Key(_) => {}
+        }
+        false
+    }
+
+    async fn run(
+        &mut self,
+        terminal: &mut DefaultTerminal,
+        updates: &mut UnboundedReceiver<Update>,
+    ) -> eyre::Result<()> {
+        let mut redraw = interval(REDRAW_INTERVAL);
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+            let update = tokio::select! {
+                update = updates.recv() => update,
+                _ = redraw.tick() => continue,
+            };
+            let Some(update) = update else {
+                return Ok(());
+            };
+
+            // Quoting takes a while, so catch up on everything that queued meanwhile first
+            let (mut reload, mut requote) = (false, false);
+            let mut pending = vec![update];
+            while let Ok(update) = updates.try_recv() {
+                pending.push(update);
+            }
+            for update in pending {
+                if self.apply(update, &mut reload, &mut requote) {
+                    return Ok(());
+                }
+            }
+            if reload {
+                self.load();
+            } else if requote {
+                self.quote();
+            }
+        }
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let endpoints = self.pool.health();
+        let [header, quotes, health, footer] = Layout::vertical([
+            Constraint::Length(4),
+            Constraint::Fill(1),
+            Constraint::Length(endpoints.len() as u16 + 4),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        let side = match self.direction {
+            SwapDirection::SolToUsdc => "selling SOL, best bid first",
+            SwapDirection::UsdcToSol => "buying SOL, best ask first",
+        };
+        let ladder: Vec<String> = self.ladders[self.ladder].iter().map(|s| s.to_string()).collect();
+        let mut snapshot = match &self.quotes {
+            Some(quotes) => vec![
+                Span::raw(format!("Snapshot slot {}", quotes.slot)).bold(),
+                Span::raw(format!(" (quoted {})", format_ago(Some(quotes.loaded_at)))),
+            ],
+            None => vec![Span::raw("No snapshot yet")],
+        };
+        if let Some(latest) = self.latest_slot {
+            snapshot.push(Span::raw(format!(" · latest fetched {latest}")));
+        }
+        if self.frozen {
+            snapshot.push(Span::raw(" · FROZEN").fg(Color::Cyan).bold());
+        }
+        let mut lines = vec![
+            Line::from(snapshot),
+            Line::from(format!("{side} · ladder {} SOL", ladder.join(", "))),
+        ];
+        if let Some(error) = &self.quote_error {
+            lines.push(Line::from(error.as_str()).fg(Color::Red));
+        }
+        frame
+            .render_widget(Paragraph::new(lines).block(Block::bordered().title(" SolFi ")), header);
+
+        frame.render_widget(self.quotes_table(), quotes);
+        frame.render_widget(self.health_table(endpoints), health);
+        frame.render_widget(
+            Paragraph::new("d direction · l ladder · space freeze · q quit").dim(),
+            footer,
+        );
+    }
+
+    fn quotes_table(&self) -> Table<'_> {
+        let header = Row::new(["Size (SOL)", "Market", "Bid", "Ask", "Spread (bps)", "Cutoff in"])
+            .style(Style::new().add_modifier(Modifier::BOLD));
+        let widths = [
+            Constraint::Length(11),
+            Constraint::Length(45),
+            Constraint::Length(10),
+            Constraint::Length(10),
+            Constraint::Length(13),
+            Constraint::Length(12),
+        ];
+        let Some(quotes) = &self.quotes else {
+            return Table::new(Vec::<Row>::new(), widths).header(header).block(Block::bordered());
+        };
+
+        let mut rows = vec![];
+        for size in &self.ladders[self.ladder] {
+            let mut group: Vec<&QuoteRow> =
+                quotes.rows.iter().filter(|r| r.size == *size).collect();
+            // Best first for the side being looked at, markets that don't quote last
+            let key = |row: &QuoteRow| match self.direction {
+                SwapDirection::SolToUsdc => row.bid.map(|bid| -bid),
+                SwapDirection::UsdcToSol => row.ask,
+            };
+            group.sort_by(|a, b| match (key(a), key(b)) {
+                (Some(a), Some(b)) => a.total_cmp(&b),
+                (a, b) => b.is_some().cmp(&a.is_some()).reverse(),
+            });
+
+            for (i, row) in group.into_iter().enumerate() {
+                let cutoff =
+                    quotes.cutoffs.iter().find(|(m, _)| *m == row.market).and_then(|c| c.1);
+                let slots_left = cutoff.map(|cutoff| cutoff as i64 - quotes.slot as i64);
+                let cutoff_style = match slots_left {
+                    Some(left) if left <= 0 => Style::new().fg(Color::Red),
+                    Some(left) if left <= CUTOFF_WARNING_SLOTS => Style::new().fg(Color::Yellow),
+                    _ => Style::new(),
+                };
+                let best = i == 0 && key(row).is_some();
+                let style = if best { Style::new().fg(Color::Green) } else { Style::new() };
+                rows.push(
+                    Row::new([
+                        if i == 0 { size.to_string() } else { String::new() }.into(),
+                        row.market.to_string().into(),
+                        format_price(row.bid).into(),
+                        format_price(row.ask).into(),
+                        row.spread_bps().map_or("-".to_string(), |s| format!("{s:.2}")).into(),
+                        Span::styled(
+                            slots_left.map_or("-".to_string(), |left| format!("{left} slots")),
+                            cutoff_style,
+                        ),
+                    ])
+                    .style(style),
+                );
+            }
+        }
+        Table::new(rows, widths).header(header).block(Block::bordered().title(" Quotes "))
+    }
+
+    fn health_table(&self, endpoints: Vec<(String, solfi_sim::rpc::EndpointHealth)>) -> Table<'_> {
+        let rows = endpoints.into_iter().map(|(url, health)| {
+            let style =
+                if health.failures > 0 { Style::new().fg(Color::Red) } else { Style::new() };
+            Row::new([
+                url,
+                health.slot.map_or("-".to_string(), |s| s.to_string()),
+                health.latency.map_or("-".to_string(), |l| format!("{} ms", l.as_millis())),
+                health.failures.to_string(),
+            ])
+            .style(style)
+        });
+        let summary = format!(
+            " Fetch: {} ok, {} failed, last {}{} ",
+            self.fetches,
+            self.failed_fetches,
+            format_ago(self.last_fetch),
+            self.last_fetch_error.as_ref().map_or(String::new(), |e| format!(" · {e}")),
+        );
+        Table::new(
+            rows,
+            [
+                Constraint::Fill(1),
+                Constraint::Length(12),
+                Constraint::Length(10),
+                Constraint::Length(9),
+            ],
+        )
+        .header(
+            Row::new(["Endpoint", "Slot", "Latency", "Failures"])
+                .style(Style::new().add_modifier(Modifier::BOLD)),
+        )
+        .block(Block::bordered().title(summary))
+    }
+}
+
+/// Shows live quotes in the terminal, refreshed on every snapshot the fetch loop saves: polled
+/// every `fetch_interval_ms` or, given a `ws_url`, followed over the PubSub API there.
+pub async fn run_dashboard(
+    rpc_urls: Vec<String>,
+    fetch_interval_ms: u64,
+    ws_url: Option<String>,
+) -> eyre::Result<()> {
+    let pool = Arc::new(RpcPool::new(&rpc_urls)?);
+    let (tx, mut updates) = unbounded_channel();
+
+    let fetched = tx.clone();
+    let report = move |result| {
+        let _ = fetched.send(Update::Fetched(result));
+    };
+    match ws_url {
+        Some(ws_url) => {
+            tokio::spawn(subscribe_state_task(pool.clone(), ws_url, fetch_interval_ms, report))
+        }
+        None => tokio::spawn(fetch_state_task(pool.clone(), fetch_interval_ms, report)),
+    };
+    std::thread::spawn(move || {
+        while let Ok(event) = event::read() {
+            if let Event::Key(key) = event
+                && key.kind == KeyEventKind::Press
+                && tx.send(Update::Key(key.code)).is_err()
+            {
+                break;
+            }
+        }
+    });
+
+    let mut dashboard = Dashboard::new(pool);
+    // Show whatever snapshot is already saved while the first fetch runs
+    dashboard.load();
+    let mut terminal = ratatui::init();
+    let result = dashboard.run(&mut terminal, &mut updates).await;
+    ratatui::restore();
+    result
+}
@@ -0,0 +1,55 @@
+use crate::types::SimError;
+use litesvm::error::LiteSVMError;
+use solana_pubkey::Pubkey;
+
+/// Errors returned by the library.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+
+    #[error(transparent)]
+    Toml(#[from] toml::de::Error),
+
+    /// The snapshot doesn't have an account the operation needs
+    #[error("account {0} is not in the snapshot")]
+    MissingAccount(Pubkey),
+
+    /// An account couldn't be decoded as what it was expected to be
+    #[error("account {address} is too short: need {needed} bytes, have {len}")]
+    AccountTooShort { address: Pubkey, needed: usize, len: usize },
+
+    /// LiteSVM rejected an account or program
+    #[error("failed to set up the SVM: {0}")]
+    Svm(String),
+
+    /// The simulated transaction failed
+    #[error("swap failed: {0}")]
+    Swap(SimError),
+
+    /// A balance moved the opposite way a successful swap should move it
+    #[error("unexpected balance change: {0}")]
+    BalanceChange(String),
+
+    /// Not enough usable samples to fit a quoting curve
+    #[error("cannot fit curve: {0}")]
+    Fit(&'static str),
+
+    /// No RPC endpoint returned a usable response
+    #[error("rpc request failed: {0}")]
+    Rpc(String),
+
+    #[error("{0}")]
+    InvalidInput(String),
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+impl From<LiteSVMError> for Error {
+    fn from(err: LiteSVMError) -> Self {
+        Error::Svm(err.to_string())
+    }
+}
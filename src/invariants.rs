@@ -0,0 +1,256 @@
+use crate::constants::{SOL_DECIMALS, USDC_DECIMALS};
+use crate::error::{Error, Result};
+use crate::simulator::Simulator;
+use crate::swap::SwapDirection;
+use serde::Serialize;
+use std::fmt;
+
+/// A property every market's quotes should have. Breaking one points at a program upgrade that
+/// changed the curve or a snapshot that doesn't hold a consistent market state.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Invariant {
+    /// Swapping more never pays out less
+    MonotonicOutput,
+    /// Each extra unit in pays out no more than the one before
+    WorseningPrice,
+    /// Swapping and swapping the output back never ends with more than the start
+    NoRoundTripProfit,
+    /// Selling and buying a similar size are priced consistently: they don't cross, are no
+    /// further apart than allowed at the smallest size, and either both quote or neither does
+    DirectionsAgree,
+}
+
+impl Invariant {
+    pub const ALL: [Invariant; 4] = [
+        Invariant::MonotonicOutput,
+        Invariant::WorseningPrice,
+        Invariant::NoRoundTripProfit,
+        Invariant::DirectionsAgree,
+    ];
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            Invariant::MonotonicOutput => "output doesn't decrease as input grows",
+            Invariant::WorseningPrice => "marginal price gets worse with size",
+            Invariant::NoRoundTripProfit => "a round trip in one market doesn't make money",
+            Invariant::DirectionsAgree => "quotes in the two directions agree",
+        }
+    }
+}
+
+impl fmt::Display for Invariant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Invariant::MonotonicOutput => "monotonic-output",
+            Invariant::WorseningPrice => "worsening-price",
+            Invariant::NoRoundTripProfit => "no-round-trip-profit",
+            Invariant::DirectionsAgree => "directions-agree",
+        };
+        f.write_str(name)
+    }
+}
+
+/// A market and size where an invariant doesn't hold.
+#[derive(Clone, Debug, Serialize)]
+pub struct Violation {
+    pub invariant: Invariant,
+    pub market: String,
+    pub direction: SwapDirection,
+    /// Input amount (UI units of the direction's input token)
+    pub size: f64,
+    pub detail: String,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct InvariantOptions {
+    /// Widest spread between the two directions allowed at the smallest size
+    pub max_spread_bps: f64,
+    /// How much a marginal price may improve before it counts, for rounding inside the program
+    pub tolerance_bps: f64,
+}
+
+impl Default for InvariantOptions {
+    fn default() -> Self {
+        Self { max_spread_bps: 100.0, tolerance_bps: 0.01 }
+    }
+}
+
+/// Smallest output change a swap can make, in UI units of the direction's output token.
+fn output_unit(direction: SwapDirection) -> f64 {
+    let decimals = match direction {
+        SwapDirection::SolToUsdc => USDC_DECIMALS,
+        SwapDirection::UsdcToSol => SOL_DECIMALS,
+    };
+    10f64.powi(-decimals)
+}
+
+/// USDC per SOL of swapping `amount_in` for `amount_out`.
+fn price(direction: SwapDirection, amount_in: f64, amount_out: f64) -> f64 {
+    match direction {
+        SwapDirection::SolToUsdc => amount_out / amount_in,
+        SwapDirection::UsdcToSol => amount_in / amount_out,
+    }
+}
+
+/// Checks the curve `quote` gives for `market` at `sol_sizes`. USDC is swapped at the same sizes
+/// valued at the price of the smallest SOL size that sells. `quote` returns the output of a swap
+/// in UI units, or `None` if the swap fails.
+pub fn check_curve(
+    market: &str,
+    sol_sizes: &[f64],
+    options: InvariantOptions,
+    mut quote: impl FnMut(SwapDirection, f64) -> Result<Option<f64>>,
+) -> Result<Vec<Violation>> {
+    let mut sol_sizes: Vec<f64> = sol_sizes.iter().copied().filter(|s| *s > 0.0).collect();
+    sol_sizes.sort_by(f64::total_cmp);
+    sol_sizes.dedup();
+    let mut violations = vec![];
+    let mut violation = |invariant, direction, size, detail: String| {
+        violations.push(Violation {
+            invariant,
+            market: market.to_string(),
+            direction,
+            size,
+            detail,
+        })
+    };
+
+    let mut sells = vec![];
+    for &size in &sol_sizes {
+        sells.push((size, quote(SwapDirection::SolToUsdc, size)?));
+    }
+    let Some(bid) = sells.iter().find_map(|(size, out)| Some(out.filter(|o| *o > 0.0)? / size))
+    else {
+        if let Some(&size) = sol_sizes.first() {
+            violation(
+                Invariant::DirectionsAgree,
+                SwapDirection::SolToUsdc,
+                size,
+                "no size sells, nothing to compare buys with".into(),
+            );
+        }
+        return Ok(violations);
+    };
+    let mut buys = vec![];
+    for &size in &sol_sizes {
+        let usdc = size * bid;
+        buys.push((usdc, quote(SwapDirection::UsdcToSol, usdc)?));
+    }
+
+    for (direction, quotes) in
+        [(SwapDirection::SolToUsdc, &sells), (SwapDirection::UsdcToSol, &buys)]
+    {
+        let unit = output_unit(direction);
+        let quoted: Vec<(f64, f64)> =
+            quotes.iter().filter_map(|(size, out)| Some((*size, (*out)?))).collect();
+        for pair in quoted.windows(2) {
+            let [(prev_in, prev_out), (size, out)] = [pair[0], pair[1]];
+            if out < prev_out {
+                violation(
+                    Invariant::MonotonicOutput,
+                    direction,
+                    size,
+                    format!("pays {out} for {size}, less than {prev_out} for {prev_in}"),
+                );
+            }
+        }
+        // Outputs are rounded down to whole units, so each marginal rate can be off by up to two
+        // units over its step, on top of whatever the program rounds along the way.
+        for triple in quoted.windows(3) {
+            let [(in_0, out_0), (in_1, out_1), (in_2, out_2)] = [triple[0], triple[1], triple[2]];
+            let before = (out_1 - out_0) / (in_1 - in_0);
+            let after = (out_2 - out_1) / (in_2 - in_1);
+            let slack = 2.0 * unit / (in_1 - in_0)
+                + 2.0 * unit / (in_2 - in_1)
+                + before * options.tolerance_bps / 10_000.0;
+            if after > before + slack {
+                violation(
+                    Invariant::WorseningPrice,
+                    direction,
+                    in_2,
+                    format!(
+                        "marginal price improves from {:.6} to {:.6} USDC per SOL between {in_1} \
+                         and {in_2}",
+                        price(direction, 1.0, before),
+                        price(direction, 1.0, after),
+                    ),
+                );
+            }
+        }
+
+        for &(size, out) in &quoted {
+            let back_direction = direction.opposite();
+            let Some(back) = quote(back_direction, out)? else {
+                continue;
+            };
+            if back > size {
+                violation(
+                    Invariant::NoRoundTripProfit,
+                    direction,
+                    size,
+                    format!("{size} in, {out} out, {back} back"),
+                );
+            }
+        }
+    }
+
+    for (i, ((sol, usdc_out), (usdc, sol_out))) in sells.iter().zip(&buys).enumerate() {
+        match (usdc_out, sol_out) {
+            (Some(usdc_out), Some(sol_out)) => {
+                let bid = price(SwapDirection::SolToUsdc, *sol, *usdc_out);
+                let ask = price(SwapDirection::UsdcToSol, *usdc, *sol_out);
+                let spread_bps = (ask - bid) / ((ask + bid) / 2.0) * 10_000.0;
+                if ask < bid {
+                    violation(
+                        Invariant::DirectionsAgree,
+                        SwapDirection::UsdcToSol,
+                        *usdc,
+                        format!("buys at {ask:.6}, below the {bid:.6} selling {sol} SOL gets"),
+                    );
+                } else if i == 0 && spread_bps > options.max_spread_bps {
+                    violation(
+                        Invariant::DirectionsAgree,
+                        SwapDirection::UsdcToSol,
+                        *usdc,
+                        format!(
+                            "buys at {ask:.6} and sells {sol} SOL at {bid:.6}, {spread_bps:.2} bps \
+                             apart"
+                        ),
+                    );
+                }
+            }
+            (Some(_), None) if i == 0 => violation(
+                Invariant::DirectionsAgree,
+                SwapDirection::UsdcToSol,
+                *usdc,
+                format!("{sol} SOL sells but {usdc} USDC doesn't buy"),
+            ),
+            _ => {}
+        }
+    }
+
+    Ok(violations)
+}
+
+/// Checks every market in `simulator` at `sol_sizes`, see [`check_curve`].
+pub fn check_invariants(
+    simulator: &Simulator,
+    sol_sizes: &[f64],
+    options: InvariantOptions,
+) -> Result<Vec<Violation>> {
+    let mut violations = vec![];
+    for (_, market) in simulator.markets() {
+        violations.extend(check_curve(
+            &market.to_string(),
+            sol_sizes,
+            options,
+            |direction, amount| match simulator.quote(&market, direction, amount) {
+                Ok(out) => Ok(Some(out)),
+                Err(Error::Swap(_)) => Ok(None),
+                Err(e) => Err(e),
+            },
+        )?);
+    }
+    Ok(violations)
+}
@@ -0,0 +1,44 @@
+//! Local simulation of SolFi swaps with [LiteSVM](https://github.com/LiteSVM/litesvm).
+//!
+//! A [`Simulator`] loads a snapshot of the SolFi markets (as written by `solfi-sim
+//! fetch-accounts`) together with the program and runs swaps against in-memory copies of it:
+//!
+//! ```no_run
+//! use solfi_sim::{Simulator, SwapDirection};
+//! use std::path::Path;
+//!
+//! let simulator = Simulator::load(Path::new("data"))?;
+//! if let Some(best) = simulator.route(SwapDirection::SolToUsdc, 10.0)? {
+//!     println!("{} USDC on {}", best.out_amount.unwrap_or_default(), best.market);
+//! }
+//! # Ok::<(), solfi_sim::Error>(())
+//! ```
+//!
+//! Nothing in the library prints; the `solfi-sim` binary is a thin layer over it.
+
+pub mod config;
+pub mod constants;
+pub mod error;
+pub mod invariants;
+pub mod market;
+#[cfg(feature = "mock-rpc")]
+pub mod mock_rpc;
+pub mod pubsub;
+pub mod quoter;
+pub mod reference;
+pub mod rpc;
+pub mod simulator;
+pub mod swap;
+pub mod types;
+pub mod utils;
+pub mod venue;
+
+pub use config::Config;
+pub use error::{Error, Result};
+pub use market::MarketState;
+pub use reference::{ReferenceKind, ReferenceModel, ReferenceParams};
+pub use simulator::{
+    BuyQuote, ComputeBudgetOptions, Simulator, SwapCosts, SwapHarness, SwapResult,
+};
+pub use swap::{SwapDirection, create_swap_ix};
+pub use venue::{ConfiguredVenue, Solfi, Venue};
@@ -1,53 +1,213 @@
 mod args;
 mod cmd;
-mod constants;
+mod dashboard;
 mod service;
-mod swap;
-mod types;
-mod utils;
+mod trace;
 
 use crate::args::{App, Command};
-use crate::cmd::{calculate_spread, display_cutoffs, fetch_and_persist_accounts, simulate};
-use crate::constants::DEFAULT_RPC_URL;
+use crate::cmd::{
+    BuildTxOptions, SensitivityOptions, SimulateOptions, approx_quote, build_book, build_tx,
+    calculate_spread, check_invariants, decode_tx, detect_arbs, display_cutoffs,
+    explore_instruction, fetch_and_persist_accounts, fit_curves, markout, plot, sensitivity,
+    simulate_with, trace_swap, validate,
+};
+use crate::dashboard::run_dashboard;
 use crate::service::run_service;
 use clap::Parser;
 use dotenv::dotenv;
+use eyre::eyre;
+use solfi_sim::config::CONFIG_FILE;
+use solfi_sim::constants::DEFAULT_RPC_URL;
+use solfi_sim::pubsub;
+use solfi_sim::{ComputeBudgetOptions, Config, ReferenceParams};
+use std::sync::OnceLock;
 use tracing_subscriber::layer::SubscriberExt;
 use tracing_subscriber::util::SubscriberInitExt;
 use tracing_subscriber::{EnvFilter, fmt};
 
 #[tokio::main]
 async fn main() -> eyre::Result<()> {
-    tracing_subscriber::registry()
-        .with(fmt::layer())
-        .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")))
-        .init();
-
-    let cmd = App::parse().command;
+    let app = App::parse();
+    // Log lines would tear through the dashboard's screen
+    if !matches!(app.command, Command::Dashboard { .. }) {
+        tracing_subscriber::registry()
+            .with(fmt::layer().with_writer(std::io::stderr))
+            .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")))
+            .init();
+    }
+    let _ = CONFIG.set(load_config(&app)?);
+    let cmd = app.command;
 
     match cmd {
-        Command::FetchAccounts => {
-            let rpc_url = get_rpc_url();
-            fetch_and_persist_accounts(rpc_url).await?
+        Command::FetchAccounts { archive, venues } => {
+            fetch_and_persist_accounts(&get_rpc_urls(), archive, venues.as_deref()).await?
         }
         Command::Cutoffs => display_cutoffs(),
         Command::Spreads { starting_usdc } => calculate_spread(starting_usdc)?,
-        Command::Simulate { amount, direction, slot, ignore_errors } => {
-            simulate(direction, amount, slot, ignore_errors, true)?;
+        Command::Simulate {
+            amount,
+            direction,
+            slot,
+            ignore_errors,
+            compute_unit_limit,
+            compute_unit_price,
+            min_compute_units,
+            venues,
+            references,
+            reference_fee_bps,
+            amplification,
+            spread_bps,
+            wide,
+        } => {
+            let options = SimulateOptions {
+                budget: ComputeBudgetOptions {
+                    unit_limit: compute_unit_limit,
+                    unit_price: compute_unit_price,
+                },
+                min_compute_units,
+                venues,
+                references,
+                reference_params: ReferenceParams {
+                    fee_bps: reference_fee_bps,
+                    amplification,
+                    spread_bps,
+                },
+                wide,
+            };
+            let data_dir = &config().data_dir;
+            simulate_with(data_dir, direction, amount, slot, ignore_errors, true, &options)?;
+        }
+        Command::Book { levels, step, tick, json } => build_book(levels, step, tick, json)?,
+        Command::Arb { min_usdc, max_usdc, steps } => detect_arbs(min_usdc, max_usdc, steps)?,
+        Command::CheckInvariants {
+            min_sol,
+            max_sol,
+            steps,
+            max_spread_bps,
+            tolerance_bps,
+            json,
+        } => check_invariants(min_sol, max_sol, steps, max_spread_bps, tolerance_bps, json)?,
+        Command::Markout { reference, snapshots, sizes, horizons, local_clock } => {
+            markout(reference, snapshots, sizes, horizons, local_clock)?
+        }
+        Command::Fit { points, kind, degree, min_sol, max_sol, output } => {
+            let output = output.unwrap_or_else(|| config().curve_model_path());
+            fit_curves(points, kind, degree, min_sol, max_sol, &output)?
+        }
+        Command::Plot { chart, direction, min_sol, max_sol, points, size, output } => {
+            plot(chart, direction, min_sol, max_sol, points, size, output)?
+        }
+        Command::ApproxQuote { model, direction, amount } => {
+            let model = model.unwrap_or_else(|| config().curve_model_path());
+            approx_quote(&model, direction, amount)?
+        }
+        Command::Trace { market, direction, amount, json } => {
+            trace_swap(market, direction, amount, json)?
         }
-        Command::Service { port, fetch_interval_ms } => {
-            let rpc_url = get_rpc_url();
-            run_service(port, rpc_url, fetch_interval_ms).await?;
+        Command::Sensitivity {
+            market,
+            direction,
+            amount,
+            mode,
+            factor,
+            width,
+            start,
+            end,
+            top,
+        } => {
+            let options = SensitivityOptions { perturbation: mode, factor, width, start, end, top };
+            sensitivity(market, direction, amount, &options)?
         }
+        Command::IxExplore { market, direction, amount } => {
+            explore_instruction(market, direction, amount)?
+        }
+        Command::DecodeTx { file, json } => decode_tx(&file, json)?,
+        Command::Validate { files, snapshot } => {
+            let snapshot = snapshot.unwrap_or_else(|| config().data_dir.clone());
+            validate(&files, &snapshot)?
+        }
+        Command::BuildTx {
+            user,
+            market,
+            direction,
+            amount,
+            slippage_bps,
+            compute_unit_limit,
+            compute_unit_price,
+            blockhash,
+        } => {
+            let options =
+                BuildTxOptions { slippage_bps, compute_unit_limit, compute_unit_price, blockhash };
+            build_tx(&get_rpc_urls(), user, market, direction, amount, &options).await?
+        }
+        Command::Service { port, fetch_interval_ms, subscribe, ws_url } => {
+            let rpc_urls = get_rpc_urls();
+            let port = port.unwrap_or(config().service.port);
+            let fetch_interval_ms = fetch_interval_ms.unwrap_or(config().service.fetch_interval_ms);
+            let service = &config().service;
+            let ws_url = (subscribe || service.subscribe).then(|| {
+                ws_url.or(service.ws_url.clone()).unwrap_or_else(|| pubsub::ws_url(&rpc_urls[0]))
+            });
+            run_service(port, rpc_urls, fetch_interval_ms, ws_url).await?;
+        }
+        Command::Dashboard { fetch_interval_ms, subscribe, ws_url } => {
+            let rpc_urls = get_rpc_urls();
+            let fetch_interval_ms = fetch_interval_ms.unwrap_or(config().service.fetch_interval_ms);
+            let service = &config().service;
+            let ws_url = (subscribe || service.subscribe).then(|| {
+                ws_url.or(service.ws_url.clone()).unwrap_or_else(|| pubsub::ws_url(&rpc_urls[0]))
+            });
+            run_dashboard(rpc_urls, fetch_interval_ms, ws_url).await?;
+        }
+        #[cfg(feature = "mock-rpc")]
+        Command::MockRpc { port, script } => cmd::serve_mock_rpc(port, script.as_deref()).await?,
     }
 
     Ok(())
 }
 
-fn get_rpc_url() -> String {
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+/// Settings for this run: the defaults, overridden by the config file, then the environment, then
+/// the command line.
+pub fn config() -> &'static Config {
+    CONFIG.get_or_init(Config::default)
+}
+
+fn load_config(app: &App) -> eyre::Result<Config> {
     let _ = dotenv().ok();
-    std::env::var("RPC_URL").ok().filter(|url| !url.trim().is_empty()).unwrap_or_else(|| {
-        tracing::warn!("No RPC_URL found in env. Using {}", DEFAULT_RPC_URL);
-        DEFAULT_RPC_URL.to_string()
-    })
+    let mut config = match &app.config {
+        Some(path) => {
+            Config::load(path).map_err(|e| eyre!("invalid config {}: {e}", path.display()))?
+        }
+        None => Config::discover().map_err(|e| eyre!("invalid config {CONFIG_FILE}: {e}"))?,
+    };
+
+    if let Some(url) = env_var("RPC_URL") {
+        config.rpc_urls = vec![url];
+    }
+    if let Some(dir) = env_var("SOLFI_SIM_DATA_DIR") {
+        config.data_dir = dir.into();
+    }
+    if let Some(dir) = &app.data_dir {
+        config.data_dir = dir.clone();
+    }
+    if !app.rpc_urls.is_empty() {
+        config.rpc_urls = app.rpc_urls.clone();
+    }
+    config.validate().map_err(|e| eyre!("invalid config: {e}"))?;
+    Ok(config)
+}
+
+fn env_var(key: &str) -> Option<String> {
+    std::env::var(key).ok().filter(|value| !value.trim().is_empty())
+}
+
+/// The configured RPC endpoints, most preferred first.
+fn get_rpc_urls() -> Vec<String> {
+    let rpc_urls = config().rpc_urls.clone();
+    if rpc_urls == [DEFAULT_RPC_URL] {
+        tracing::warn!("No RPC endpoint configured. Using {}", DEFAULT_RPC_URL);
+    }
+    rpc_urls
 }
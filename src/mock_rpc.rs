@@ -0,0 +1,287 @@
+use crate::error::Result;
+use crate::types::{AccountWithAddress, FetchMetadata};
+use axum::Router;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::Json;
+use axum::routing::post;
+use base64::Engine;
+use base64::prelude::BASE64_STANDARD;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use solana_account::Account;
+use solana_pubkey::Pubkey;
+use solana_sdk::hash::hash;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+
+/// How a phase of a [`MockScript`] fails its requests.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MockFailure {
+    /// HTTP 503, like a node that's down or rate limiting
+    Http,
+    /// A JSON-RPC error, like a node that's behind
+    Rpc,
+}
+
+/// A stretch of requests answered the same way.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MockPhase {
+    /// How many requests the phase lasts, or `None` for all remaining ones
+    pub requests: Option<u64>,
+    /// Slots the node advances by on each request
+    pub advance: u64,
+    pub delay_ms: u64,
+    pub fail: Option<MockFailure>,
+}
+
+/// The phases a mock node goes through, in order. After the last one the node answers at its
+/// current slot without delays or failures.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MockScript {
+    /// Slot to start at (default: the snapshot's)
+    pub start_slot: Option<u64>,
+    pub phases: Vec<MockPhase>,
+}
+
+impl MockScript {
+    /// Reads a script from a JSON file.
+    pub fn load(path: &Path) -> Result<Self> {
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    fn phase(&self, request: u64) -> Option<&MockPhase> {
+        let mut start = 0;
+        for phase in &self.phases {
+            match phase.requests {
+                Some(requests) if request >= start + requests => start += requests,
+                _ => return Some(phase),
+            }
+        }
+        None
+    }
+}
+
+struct MockState {
+    slot: u64,
+    requests: u64,
+    delay: Duration,
+    down: bool,
+}
+
+/// A stand-in for a Solana RPC node, for testing fetching and the service offline. Answers
+/// `getSlot`, `getAccountInfo`, `getMultipleAccounts`, `getProgramAccounts`, `getBlockTime` and
+/// `getHealth` from a fixed set of accounts, at a slot that moves as scripted.
+pub struct MockRpc {
+    accounts: BTreeMap<Pubkey, Account>,
+    block_time: Option<i64>,
+    script: MockScript,
+    state: Mutex<MockState>,
+}
+
+impl MockRpc {
+    pub fn new(accounts: Vec<AccountWithAddress>, slot: u64, script: MockScript) -> Self {
+        let slot = script.start_slot.unwrap_or(slot);
+        Self {
+            accounts: accounts.into_iter().map(|a| (a.address, a.account)).collect(),
+            block_time: None,
+            script,
+            state: Mutex::new(MockState { slot, requests: 0, delay: Duration::ZERO, down: false }),
+        }
+    }
+
+    /// Serves the snapshot in `data_dir`, starting at the slot it was fetched at.
+    pub fn from_dir(data_dir: &Path, script: MockScript) -> Result<Self> {
+        let metadata = FetchMetadata::read_from(data_dir);
+        let slot = metadata.as_ref().map(|m| m.slot()).unwrap_or_default();
+        let mut mock = Self::new(AccountWithAddress::read_all_from(data_dir)?, slot, script);
+        mock.block_time = metadata.and_then(|m| m.block_time());
+        Ok(mock)
+    }
+
+    /// Answers on `listener` until the task is dropped.
+    pub async fn serve(self: Arc<Self>, listener: TcpListener) -> Result<()> {
+        let app = Router::new().route("/", post(handle)).with_state(self);
+        axum::serve(listener, app).await?;
+        Ok(())
+    }
+
+    /// Serves on a free local port in the background.
+    pub async fn start(self) -> Result<MockRpcServer> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let url = format!("http://{}", listener.local_addr()?);
+        let mock = Arc::new(self);
+        let task = tokio::spawn(mock.clone().serve(listener));
+        Ok(MockRpcServer { url, mock, task })
+    }
+
+    pub fn slot(&self) -> u64 {
+        self.state.lock().unwrap().slot
+    }
+
+    pub fn set_slot(&self, slot: u64) {
+        self.state.lock().unwrap().slot = slot;
+    }
+
+    /// Delays every answer by `delay`, on top of the script's.
+    pub fn set_delay(&self, delay: Duration) {
+        self.state.lock().unwrap().delay = delay;
+    }
+
+    /// Answers every request with a 503 while down.
+    pub fn set_down(&self, down: bool) {
+        self.state.lock().unwrap().down = down;
+    }
+
+    /// Requests answered so far.
+    pub fn requests(&self) -> u64 {
+        self.state.lock().unwrap().requests
+    }
+
+    fn result(&self, method: &str, params: &Value, slot: u64) -> std::result::Result<Value, Value> {
+        match method {
+            "getHealth" => Ok(json!("ok")),
+            "getSlot" => Ok(json!(slot)),
+            "getBlockTime" => self
+                .block_time
+                .map(|t| json!(t))
+                .ok_or_else(|| rpc_error(-32009, "Block not available")),
+            // A blockhash that changes with the slot, valid for the usual 150 blocks
+            "getLatestBlockhash" => Ok(json!({
+                "context": { "slot": slot },
+                "value": {
+                    "blockhash": hash(&slot.to_le_bytes()).to_string(),
+                    "lastValidBlockHeight": slot + 150,
+                },
+            })),
+            "getAccountInfo" => {
+                let address = parse_pubkey(&params[0])?;
+                let value = self.accounts.get(&address).map_or(Value::Null, ui_account);
+                Ok(json!({ "context": { "slot": slot }, "value": value }))
+            }
+            "getMultipleAccounts" => {
+                let addresses = params[0].as_array().ok_or_else(invalid_params)?;
+                let value = addresses
+                    .iter()
+                    .map(|address| {
+                        let address = parse_pubkey(address)?;
+                        Ok(self.accounts.get(&address).map_or(Value::Null, ui_account))
+                    })
+                    .collect::<std::result::Result<Vec<_>, Value>>()?;
+                Ok(json!({ "context": { "slot": slot }, "value": value }))
+            }
+            "getProgramAccounts" => {
+                let program = parse_pubkey(&params[0])?;
+                let filters = params[1]["filters"].as_array().cloned().unwrap_or_default();
+                let value: Vec<Value> = self
+                    .accounts
+                    .iter()
+                    .filter(|(_, account)| account.owner == program)
+                    .filter(|(_, account)| filters.iter().all(|f| matches_filter(f, &account.data)))
+                    .map(|(address, account)| {
+                        json!({ "pubkey": address.to_string(), "account": ui_account(account) })
+                    })
+                    .collect();
+                if params[1]["withContext"].as_bool().unwrap_or_default() {
+                    Ok(json!({ "context": { "slot": slot }, "value": value }))
+                } else {
+                    Ok(json!(value))
+                }
+            }
+            _ => Err(rpc_error(-32601, "Method not found")),
+        }
+    }
+}
+
+/// A [`MockRpc`] served in the background, stopped when dropped.
+pub struct MockRpcServer {
+    pub url: String,
+    mock: Arc<MockRpc>,
+    task: JoinHandle<Result<()>>,
+}
+
+impl std::ops::Deref for MockRpcServer {
+    type Target = MockRpc;
+
+    fn deref(&self) -> &MockRpc {
+        &self.mock
+    }
+}
+
+impl Drop for MockRpcServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+fn rpc_error(code: i64, message: &str) -> Value {
+    json!({ "code": code, "message": message })
+}
+
+fn invalid_params() -> Value {
+    rpc_error(-32602, "Invalid params")
+}
+
+fn parse_pubkey(value: &Value) -> std::result::Result<Pubkey, Value> {
+    value.as_str().and_then(|s| s.parse().ok()).ok_or_else(invalid_params)
+}
+
+/// Base64 whatever encoding was asked for; clients decode by the encoding named in the answer.
+fn ui_account(account: &Account) -> Value {
+    json!({
+        "lamports": account.lamports,
+        "data": [BASE64_STANDARD.encode(&account.data), "base64"],
+        "owner": account.owner.to_string(),
+        "executable": account.executable,
+        "rentEpoch": account.rent_epoch,
+        "space": account.data.len(),
+    })
+}
+
+fn matches_filter(filter: &Value, data: &[u8]) -> bool {
+    if let Some(size) = filter["dataSize"].as_u64() {
+        return data.len() as u64 == size;
+    }
+    let memcmp = &filter["memcmp"];
+    let (Some(offset), Some(bytes)) = (memcmp["offset"].as_u64(), memcmp["bytes"].as_str()) else {
+        return false;
+    };
+    let bytes = match memcmp["encoding"].as_str() {
+        Some("base64") => BASE64_STANDARD.decode(bytes).ok(),
+        _ => bs58::decode(bytes).into_vec().ok(),
+    };
+    bytes.is_some_and(|bytes| data.get(offset as usize..).is_some_and(|d| d.starts_with(&bytes)))
+}
+
+async fn handle(
+    State(mock): State<Arc<MockRpc>>,
+    Json(req): Json<Value>,
+) -> std::result::Result<Json<Value>, StatusCode> {
+    let (slot, delay, failure) = {
+        let mut state = mock.state.lock().unwrap();
+        let phase = mock.script.phase(state.requests).cloned().unwrap_or_default();
+        state.requests += 1;
+        state.slot += phase.advance;
+        let failure = if state.down { Some(MockFailure::Http) } else { phase.fail };
+        (state.slot, state.delay + Duration::from_millis(phase.delay_ms), failure)
+    };
+    tokio::time::sleep(delay).await;
+
+    let result = match failure {
+        Some(MockFailure::Http) => return Err(StatusCode::SERVICE_UNAVAILABLE),
+        Some(MockFailure::Rpc) => Err(rpc_error(-32005, "Node is behind")),
+        None => mock.result(req["method"].as_str().unwrap_or_default(), &req["params"], slot),
+    };
+    Ok(Json(match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "result": result, "id": req["id"] }),
+        Err(error) => json!({ "jsonrpc": "2.0", "error": error, "id": req["id"] }),
+    }))
+}
@@ -0,0 +1,240 @@
+use crate::constants::SLOT_DURATION_MS;
+use crate::error::{Error, Result};
+use crate::rpc::RpcPool;
+use crate::types::AccountWithAddress;
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{Value, json};
+use solana_account::Account;
+use solana_account_decoder_client_types::UiAccount;
+use solana_pubkey::Pubkey;
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+use tokio::time::{Instant, interval, timeout};
+use tokio_tungstenite::connect_async;
+use tokio_tungstenite::tungstenite::Message;
+
+/// A slot's updates are taken as complete once a later slot shows up, or after this long without
+/// a notification.
+const SLOT_SETTLE: Duration = Duration::from_millis(SLOT_DURATION_MS);
+
+/// A connection that's silent for this long is treated as dropped.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Account states as of the end of a slot.
+#[derive(Clone, Debug)]
+pub struct Snapshot {
+    pub slot: u64,
+    pub accounts: Vec<AccountWithAddress>,
+}
+
+/// Folds account notifications into per-slot snapshots. Notifications for a slot can arrive
+/// interleaved with each other, so a slot's snapshot is only produced once it's complete.
+pub struct SnapshotBuilder {
+    accounts: BTreeMap<Pubkey, Account>,
+    /// Slot of the last snapshot produced
+    slot: u64,
+    /// Slot of the updates applied since then
+    pending: Option<u64>,
+}
+
+impl SnapshotBuilder {
+    /// Starts from `seed`, usually a poll taken just before subscribing.
+    pub fn new(seed: &Snapshot) -> Self {
+        let accounts = seed.accounts.iter().map(|a| (a.address, a.account.clone())).collect();
+        Self { accounts, slot: seed.slot, pending: None }
+    }
+
+    /// Applies an update and returns the previous slot's snapshot if the update starts a new
+    /// slot. Updates older than the state already held are dropped.
+    pub fn apply(&mut self, slot: u64, address: Pubkey, account: Account) -> Option<Snapshot> {
+        if slot < self.pending.unwrap_or(self.slot) {
+            tracing::debug!("Dropping update of {address} at slot {slot}");
+            return None;
+        }
+        let done =
+            if self.pending.is_some_and(|pending| slot > pending) { self.flush() } else { None };
+        self.accounts.insert(address, account);
+        self.pending = Some(slot);
+        done
+    }
+
+    /// The snapshot of the pending slot, if there were updates since the last one.
+    pub fn flush(&mut self) -> Option<Snapshot> {
+        self.slot = self.pending.take()?;
+        Some(self.snapshot())
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        let accounts = self
+            .accounts
+            .iter()
+            .map(|(address, account)| AccountWithAddress {
+                address: *address,
+                account: account.clone(),
+            })
+            .collect();
+        Snapshot { slot: self.slot, accounts }
+    }
+}
+
+/// The PubSub endpoint of an RPC node that serves both on the same host and port.
+pub fn ws_url(rpc_url: &str) -> String {
+    if let Some(rest) = rpc_url.strip_prefix("https://") {
+        format!("wss://{rest}")
+    } else if let Some(rest) = rpc_url.strip_prefix("http://") {
+        format!("ws://{rest}")
+    } else {
+        rpc_url.to_string()
+    }
+}
+
+/// Fetches `addresses` from `pool` as a snapshot, skipping accounts that don't exist.
+pub async fn poll_snapshot(pool: &RpcPool, addresses: &[Pubkey]) -> Result<Snapshot> {
+    let (slot, accounts) = pool.get_multiple_accounts(addresses).await?;
+    let accounts = accounts
+        .into_iter()
+        .zip(addresses)
+        .filter_map(|(account, address)| {
+            Some(AccountWithAddress { address: *address, account: account? })
+        })
+        .collect();
+    Ok(Snapshot { slot, accounts })
+}
+
+fn ws_error(e: tokio_tungstenite::tungstenite::Error) -> Error {
+    Error::Rpc(format!("websocket: {e}"))
+}
+
+/// Subscribes to `addresses` over the PubSub API at `ws_url` and calls `on_snapshot` for every
+/// slot that changes one of them, starting from `seed`. Returns once the connection closes.
+pub async fn subscribe_accounts(
+    ws_url: &str,
+    addresses: &[Pubkey],
+    seed: &Snapshot,
+    mut on_snapshot: impl FnMut(Snapshot),
+) -> Result<()> {
+    let (mut ws, _) = connect_async(ws_url).await.map_err(ws_error)?;
+    for (id, address) in addresses.iter().enumerate() {
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": "accountSubscribe",
+            "params": [address.to_string(), { "encoding": "base64", "commitment": "processed" }],
+        });
+        ws.send(Message::text(request.to_string())).await.map_err(ws_error)?;
+    }
+
+    let mut subscriptions = HashMap::new();
+    let mut builder = SnapshotBuilder::new(seed);
+    let mut last_message = Instant::now();
+    loop {
+        let message = match timeout(SLOT_SETTLE, ws.next()).await {
+            Err(_) => {
+                if last_message.elapsed() >= IDLE_TIMEOUT {
+                    return Err(Error::Rpc(format!(
+                        "no messages from {ws_url} for {IDLE_TIMEOUT:?}"
+                    )));
+                }
+                if let Some(snapshot) = builder.flush() {
+                    on_snapshot(snapshot);
+                }
+                continue;
+            }
+            Ok(None) => break,
+            Ok(Some(message)) => message.map_err(ws_error)?,
+        };
+        last_message = Instant::now();
+        let text = match message {
+            Message::Text(text) => text,
+            Message::Close(_) => break,
+            _ => continue,
+        };
+        let message: Value = serde_json::from_str(&text)?;
+
+        // Subscription confirmations answer the request with the same id
+        if let Some(address) = message["id"].as_u64().and_then(|id| addresses.get(id as usize)) {
+            let Some(subscription) = message["result"].as_u64() else {
+                return Err(Error::Rpc(format!(
+                    "accountSubscribe {address} failed: {}",
+                    message["error"]
+                )));
+            };
+            subscriptions.insert(subscription, *address);
+            continue;
+        }
+
+        if message["method"] != "accountNotification" {
+            continue;
+        }
+        let params = &message["params"];
+        let Some(address) = params["subscription"].as_u64().and_then(|s| subscriptions.get(&s))
+        else {
+            continue;
+        };
+        let (Some(slot), Some(account)) = (
+            params["result"]["context"]["slot"].as_u64(),
+            serde_json::from_value::<UiAccount>(params["result"]["value"].clone())
+                .ok()
+                .and_then(|account| account.decode::<Account>()),
+        ) else {
+            tracing::warn!("Malformed notification for {address}: {params}");
+            continue;
+        };
+        if let Some(snapshot) = builder.apply(slot, *address, account) {
+            on_snapshot(snapshot);
+        }
+    }
+
+    if let Some(snapshot) = builder.flush() {
+        on_snapshot(snapshot);
+    }
+    Ok(())
+}
+
+/// How [`stream_snapshots`] paces itself.
+#[derive(Clone, Copy, Debug)]
+pub struct StreamOptions {
+    /// Poll interval while the subscription is down
+    pub poll_interval: Duration,
+    /// How long to poll after the subscription drops before subscribing again
+    pub resubscribe_after: Duration,
+}
+
+/// Keeps `on_snapshot` fed with snapshots of `addresses`: from a subscription at `ws_url` while
+/// it's up, and from polling `pool` while it's down. Never returns.
+pub async fn stream_snapshots(
+    pool: &RpcPool,
+    ws_url: &str,
+    addresses: &[Pubkey],
+    options: StreamOptions,
+    mut on_snapshot: impl FnMut(Snapshot),
+) {
+    loop {
+        // Subscriptions only report changes, so start from a poll
+        match poll_snapshot(pool, addresses).await {
+            Ok(seed) => {
+                on_snapshot(seed.clone());
+                let subscribed = subscribe_accounts(ws_url, addresses, &seed, |snapshot| {
+                    pool.advance_slot(snapshot.slot);
+                    on_snapshot(snapshot)
+                })
+                .await;
+                match subscribed {
+                    Ok(()) => tracing::warn!("Subscription to {ws_url} closed, polling"),
+                    Err(e) => tracing::warn!("Subscription to {ws_url} failed, polling: {e}"),
+                }
+            }
+            Err(e) => tracing::warn!("Failed to fetch accounts: {e}"),
+        }
+
+        let resubscribe_at = Instant::now() + options.resubscribe_after;
+        let mut interval = interval(options.poll_interval);
+        while Instant::now() < resubscribe_at {
+            interval.tick().await;
+            match poll_snapshot(pool, addresses).await {
+                Ok(snapshot) => on_snapshot(snapshot),
+                Err(e) => tracing::warn!("Failed to fetch accounts: {e}"),
+            }
+        }
+    }
+}
@@ -0,0 +1,199 @@
+use crate::error::{Error, Result};
+use crate::swap::SwapDirection;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, ValueEnum)]
+pub enum FitKind {
+    /// Linear interpolation of the average price between sampled sizes (in log space)
+    PiecewiseLinear,
+    /// Least-squares polynomial of the average price in log size
+    Polynomial,
+}
+
+/// A fitted average price (output per unit of input) as a function of `ln(amount_in)`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Fit {
+    PiecewiseLinear { log_sizes: Vec<f64>, prices: Vec<f64> },
+    Polynomial { center: f64, scale: f64, coeffs: Vec<f64> },
+}
+
+impl Fit {
+    pub fn from_samples(kind: FitKind, degree: usize, samples: &[(f64, f64)]) -> Result<Self> {
+        let points: Vec<(f64, f64)> = samples
+            .iter()
+            .filter(|(amount_in, out)| *amount_in > 0.0 && *out > 0.0)
+            .map(|(amount_in, out)| (amount_in.ln(), out / amount_in))
+            .collect();
+        if points.len() < 2 {
+            return Err(Error::Fit("need at least 2 successful samples"));
+        }
+
+        match kind {
+            FitKind::PiecewiseLinear => Ok(Fit::PiecewiseLinear {
+                log_sizes: points.iter().map(|(x, _)| *x).collect(),
+                prices: points.iter().map(|(_, y)| *y).collect(),
+            }),
+            FitKind::Polynomial => {
+                let degree = degree.min(points.len() - 1);
+                let (min, max) = points
+                    .iter()
+                    .fold((f64::MAX, f64::MIN), |(lo, hi), (x, _)| (lo.min(*x), hi.max(*x)));
+                // Map log sizes onto [-1, 1] to keep the normal equations well conditioned.
+                let center = (min + max) / 2.0;
+                let scale = ((max - min) / 2.0).max(f64::EPSILON);
+                let scaled: Vec<(f64, f64)> =
+                    points.iter().map(|(x, y)| ((x - center) / scale, *y)).collect();
+                let coeffs = least_squares(&scaled, degree)?;
+                Ok(Fit::Polynomial { center, scale, coeffs })
+            }
+        }
+    }
+
+    fn price(&self, log_size: f64) -> f64 {
+        match self {
+            Fit::PiecewiseLinear { log_sizes, prices } => {
+                let idx = log_sizes.partition_point(|x| *x <= log_size);
+                if idx == 0 {
+                    return prices[0];
+                }
+                if idx == log_sizes.len() {
+                    return prices[prices.len() - 1];
+                }
+                let (x0, x1) = (log_sizes[idx - 1], log_sizes[idx]);
+                let (y0, y1) = (prices[idx - 1], prices[idx]);
+                y0 + (y1 - y0) * (log_size - x0) / (x1 - x0)
+            }
+            Fit::Polynomial { center, scale, coeffs } => {
+                let x = (log_size - center) / scale;
+                coeffs.iter().rev().fold(0.0, |acc, c| acc * x + c)
+            }
+        }
+    }
+}
+
+/// Solves the least-squares normal equations for a polynomial of `degree` through `points`.
+fn least_squares(points: &[(f64, f64)], degree: usize) -> Result<Vec<f64>> {
+    let n = degree + 1;
+    let mut a = vec![vec![0.0; n + 1]; n];
+    for (x, y) in points {
+        let powers: Vec<f64> = (0..2 * n).map(|p| x.powi(p as i32)).collect();
+        for (row, a_row) in a.iter_mut().enumerate() {
+            for col in 0..n {
+                a_row[col] += powers[row + col];
+            }
+            a_row[n] += y * powers[row];
+        }
+    }
+
+    // Gaussian elimination with partial pivoting.
+    for col in 0..n {
+        let pivot = (col..n).max_by(|i, j| a[*i][col].abs().total_cmp(&a[*j][col].abs())).unwrap();
+        if a[pivot][col].is_nan() || a[pivot][col].abs() < f64::EPSILON {
+            return Err(Error::Fit("samples are degenerate, try fewer polynomial terms"));
+        }
+        a.swap(col, pivot);
+        for row in col + 1..n {
+            let (top, bottom) = a.split_at_mut(row);
+            let (pivot_row, target) = (&top[col], &mut bottom[0]);
+            let factor = target[col] / pivot_row[col];
+            for (t, p) in target[col..].iter_mut().zip(&pivot_row[col..]) {
+                *t -= factor * p;
+            }
+        }
+    }
+    let mut coeffs = vec![0.0; n];
+    for row in (0..n).rev() {
+        let sum: f64 = (row + 1..n).map(|k| a[row][k] * coeffs[k]).sum();
+        coeffs[row] = (a[row][n] - sum) / a[row][row];
+    }
+    Ok(coeffs)
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FittedCurve {
+    pub market: String,
+    pub direction: SwapDirection,
+    /// Smallest and largest sampled input amounts (UI units). Quotes outside this range are refused
+    /// above it and flat-extrapolated below it.
+    pub min_in: f64,
+    pub max_in: f64,
+    pub fit: Fit,
+    pub max_error_bps: Option<f64>,
+    pub mean_error_bps: Option<f64>,
+}
+
+/// Every fitted curve from one snapshot. The slot is kept so the model can be thrown away once the
+/// markets have moved on.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CurveModel {
+    pub slot: u64,
+    pub kind: FitKind,
+    pub curves: Vec<FittedCurve>,
+}
+
+impl CurveModel {
+    pub fn read(path: &Path) -> Result<Self> {
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    pub fn save_to_file(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+/// Answers quotes from a [`CurveModel`] without touching the SVM.
+pub struct ApproxQuoter {
+    model: CurveModel,
+}
+
+impl ApproxQuoter {
+    pub fn new(model: CurveModel) -> Self {
+        Self { model }
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        Ok(Self::new(CurveModel::read(path)?))
+    }
+
+    pub fn into_model(self) -> CurveModel {
+        self.model
+    }
+
+    pub fn slot(&self) -> u64 {
+        self.model.slot
+    }
+
+    /// The model only describes the snapshot it was fitted on.
+    pub fn is_stale(&self, current_slot: u64) -> bool {
+        current_slot != self.model.slot
+    }
+
+    /// Approximate output amount (UI units) for swapping `amount_in` on `market`, or `None` if the
+    /// market wasn't fitted or the amount is beyond the sampled range.
+    pub fn quote(&self, market: &str, direction: SwapDirection, amount_in: f64) -> Option<f64> {
+        let curve =
+            self.model.curves.iter().find(|c| c.market == market && c.direction == direction)?;
+        if amount_in <= 0.0 || amount_in > curve.max_in {
+            return None;
+        }
+        Some(amount_in * curve.fit.price(amount_in.ln()))
+    }
+
+    /// Best approximate output across all fitted markets.
+    pub fn best_quote(&self, direction: SwapDirection, amount_in: f64) -> Option<(&str, f64)> {
+        self.model
+            .curves
+            .iter()
+            .filter(|c| c.direction == direction)
+            .filter_map(|c| Some((c.market.as_str(), self.quote(&c.market, direction, amount_in)?)))
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+    }
+}
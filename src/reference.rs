@@ -0,0 +1,188 @@
+use crate::swap::SwapDirection;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Newton iterations are capped like Curve's own implementation.
+const MAX_ITERATIONS: usize = 255;
+const TOLERANCE: f64 = 1e-12;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum ReferenceKind {
+    /// x·y = k on the vault balances
+    ConstantProduct,
+    /// Curve's stableswap invariant, with SOL valued at the market's mid price
+    Stableswap,
+    /// Every size fills at the mid price less a fixed spread
+    FixedSpread,
+}
+
+impl fmt::Display for ReferenceKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReferenceKind::ConstantProduct => write!(f, "constant-product"),
+            ReferenceKind::Stableswap => write!(f, "stableswap"),
+            ReferenceKind::FixedSpread => write!(f, "fixed-spread"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct ReferenceParams {
+    /// Fee taken from the input by the constant-product and stableswap models
+    pub fee_bps: f64,
+    /// Stableswap amplification coefficient `A`
+    pub amplification: f64,
+    /// Spread of the fixed-spread model on either side of the mid price
+    pub spread_bps: f64,
+}
+
+impl Default for ReferenceParams {
+    fn default() -> Self {
+        Self { fee_bps: 0.0, amplification: 100.0, spread_bps: 0.0 }
+    }
+}
+
+/// A textbook pool holding the same balances as a SolFi market's vaults, to compare SolFi's
+/// curve with. Amounts are in UI units and prices in USDC per SOL.
+#[derive(Clone, Debug, Serialize)]
+pub struct ReferenceModel {
+    pub kind: ReferenceKind,
+    pub reserve_sol: f64,
+    pub reserve_usdc: f64,
+    pub mid_price: f64,
+    pub params: ReferenceParams,
+}
+
+impl ReferenceModel {
+    pub fn new(
+        kind: ReferenceKind,
+        reserve_sol: f64,
+        reserve_usdc: f64,
+        mid_price: f64,
+        params: ReferenceParams,
+    ) -> Self {
+        Self { kind, reserve_sol, reserve_usdc, mid_price, params }
+    }
+
+    /// Output for swapping `amount_in`, or `None` if the pool can't pay it out.
+    pub fn quote(&self, direction: SwapDirection, amount_in: f64) -> Option<f64> {
+        if amount_in <= 0.0 || self.reserve_sol <= 0.0 || self.reserve_usdc <= 0.0 {
+            return None;
+        }
+        let after_fee = amount_in * (1.0 - self.params.fee_bps / 10_000.0);
+        let (reserve_out, out) = match (self.kind, direction) {
+            (ReferenceKind::ConstantProduct, SwapDirection::SolToUsdc) => {
+                let out = self.reserve_usdc * after_fee / (self.reserve_sol + after_fee);
+                (self.reserve_usdc, out)
+            }
+            (ReferenceKind::ConstantProduct, SwapDirection::UsdcToSol) => {
+                let out = self.reserve_sol * after_fee / (self.reserve_usdc + after_fee);
+                (self.reserve_sol, out)
+            }
+            // Both sides in USDC terms, so the curve is flattest at the mid price.
+            (ReferenceKind::Stableswap, SwapDirection::SolToUsdc) => {
+                let x = self.reserve_sol * self.mid_price;
+                let out = stableswap_out(
+                    x,
+                    self.reserve_usdc,
+                    after_fee * self.mid_price,
+                    self.params.amplification,
+                )?;
+                (self.reserve_usdc, out)
+            }
+            (ReferenceKind::Stableswap, SwapDirection::UsdcToSol) => {
+                let x = self.reserve_sol * self.mid_price;
+                let out =
+                    stableswap_out(self.reserve_usdc, x, after_fee, self.params.amplification)?;
+                (self.reserve_sol, out / self.mid_price)
+            }
+            (ReferenceKind::FixedSpread, direction) => {
+                let spread = 1.0 - self.params.spread_bps / 10_000.0;
+                match direction {
+                    SwapDirection::SolToUsdc => {
+                        (self.reserve_usdc, amount_in * self.mid_price * spread)
+                    }
+                    SwapDirection::UsdcToSol => {
+                        (self.reserve_sol, amount_in / self.mid_price * spread)
+                    }
+                }
+            }
+        };
+        (out.is_finite() && out > 0.0 && out < reserve_out).then_some(out)
+    }
+}
+
+/// Stableswap invariant `D` of a two-coin pool.
+fn stableswap_d(x: f64, y: f64, amplification: f64) -> Option<f64> {
+    let ann = amplification * 4.0;
+    let sum = x + y;
+    let mut d = sum;
+    for _ in 0..MAX_ITERATIONS {
+        let d_p = d * d * d / (4.0 * x * y);
+        let previous = d;
+        d = (ann * sum + 2.0 * d_p) * d / ((ann - 1.0) * d + 3.0 * d_p);
+        if (d - previous).abs() <= TOLERANCE * d {
+            return Some(d);
+        }
+    }
+    None
+}
+
+/// Balance of the other coin that keeps `D` once one coin's balance is `x`.
+fn stableswap_y(x: f64, d: f64, amplification: f64) -> Option<f64> {
+    let ann = amplification * 4.0;
+    let c = d * d / (2.0 * x) * d / (2.0 * ann);
+    let b = x + d / ann;
+    let mut y = d;
+    for _ in 0..MAX_ITERATIONS {
+        let previous = y;
+        y = (y * y + c) / (2.0 * y + b - d);
+        if (y - previous).abs() <= TOLERANCE * y {
+            return Some(y);
+        }
+    }
+    None
+}
+
+/// Output for adding `dx` to a pool holding `x` of the input coin and `y` of the output coin.
+fn stableswap_out(x: f64, y: f64, dx: f64, amplification: f64) -> Option<f64> {
+    let d = stableswap_d(x, y, amplification)?;
+    Some(y - stableswap_y(x + dx, d, amplification)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64, relative: f64) {
+        assert!(
+            (actual - expected).abs() <= relative * expected.abs(),
+            "{actual} isn't within {relative} of {expected}"
+        );
+    }
+
+    #[test]
+    fn stableswap_newton_converges() {
+        for (x, y) in [(1e6, 1e6), (1e6, 2.5e6), (30e6, 1e3)] {
+            for amplification in [1.0, 100.0, 5000.0] {
+                let d = stableswap_d(x, y, amplification).unwrap();
+                // A·n^n·(x + y) + D = A·n^n·D + D^(n+1) / (n^n·x·y) with n = 2
+                let ann = amplification * 4.0;
+                assert_close(ann * (x + y) + d, ann * d + d * d * d / (4.0 * x * y), 1e-9);
+                assert_close(stableswap_y(x, d, amplification).unwrap(), y, 1e-9);
+            }
+        }
+        assert_close(stableswap_d(1e6, 1e6, 100.0).unwrap(), 2e6, 1e-12);
+    }
+
+    #[test]
+    fn stableswap_matches_constant_product_at_low_amplification() {
+        let (x, y) = (2e6, 1e6);
+        for dx in [1.0, 1e4, 1e6] {
+            let constant_product = y * dx / (x + dx);
+            assert_close(stableswap_out(x, y, dx, 1e-6).unwrap(), constant_product, 1e-4);
+        }
+    }
+}
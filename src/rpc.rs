@@ -0,0 +1,200 @@
+use crate::error::{Error, Result};
+use solana_account::Account;
+use solana_pubkey::Pubkey;
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::hash::Hash;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::task::JoinSet;
+
+/// Endpoints this many slots behind the highest one seen still count as caught up, so the
+/// fastest of them is used rather than whichever happens to be a slot ahead.
+const SLOT_TOLERANCE: u64 = 2;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// What the pool knows about an endpoint from its last health check or request.
+#[derive(Clone, Debug, Default)]
+pub struct EndpointHealth {
+    /// Latest slot the endpoint reported
+    pub slot: Option<u64>,
+    pub latency: Option<Duration>,
+    /// Failed requests since the last success
+    pub failures: u32,
+}
+
+struct Endpoint {
+    url: String,
+    client: RpcClient,
+    health: Mutex<EndpointHealth>,
+}
+
+impl Endpoint {
+    fn succeeded(&self, slot: u64, latency: Duration) {
+        let mut health = self.health.lock().unwrap();
+        *health = EndpointHealth { slot: Some(slot), latency: Some(latency), failures: 0 };
+    }
+
+    fn failed(&self) {
+        self.health.lock().unwrap().failures += 1;
+    }
+}
+
+/// Several RPC endpoints used as one: requests go to the caught-up endpoint with the lowest
+/// latency and fail over to the next on errors. Account snapshots are only accepted if their
+/// context slot isn't behind the last one accepted, so a lagging endpoint can't roll state back.
+pub struct RpcPool {
+    endpoints: Vec<Arc<Endpoint>>,
+    last_slot: AtomicU64,
+}
+
+impl RpcPool {
+    /// A pool over `urls`, most preferred first. Until a health check or request has measured
+    /// them, endpoints are tried in that order.
+    pub fn new(urls: &[String]) -> Result<Self> {
+        Self::with_timeout(urls, REQUEST_TIMEOUT)
+    }
+
+    /// Same as [`RpcPool::new`] with a per-request `timeout`.
+    pub fn with_timeout(urls: &[String], timeout: Duration) -> Result<Self> {
+        if urls.is_empty() {
+            return Err(Error::InvalidInput("no RPC endpoints".into()));
+        }
+        let endpoints = urls
+            .iter()
+            .map(|url| {
+                Arc::new(Endpoint {
+                    url: url.clone(),
+                    client: RpcClient::new_with_timeout_and_commitment(
+                        url.clone(),
+                        timeout,
+                        CommitmentConfig::confirmed(),
+                    ),
+                    health: Mutex::default(),
+                })
+            })
+            .collect();
+        Ok(Self { endpoints, last_slot: AtomicU64::new(0) })
+    }
+
+    /// Asks every endpoint for its slot at once and records the slot and latency.
+    pub async fn health_check(&self) {
+        let mut checks = JoinSet::new();
+        for endpoint in &self.endpoints {
+            let endpoint = endpoint.clone();
+            checks.spawn(async move {
+                let start = Instant::now();
+                match endpoint.client.get_slot().await {
+                    Ok(slot) => endpoint.succeeded(slot, start.elapsed()),
+                    Err(e) => {
+                        tracing::debug!("Health check of {} failed: {e}", endpoint.url);
+                        endpoint.failed();
+                    }
+                }
+            });
+        }
+        checks.join_all().await;
+    }
+
+    /// Every endpoint's URL and health, in the order requests would try them.
+    pub fn health(&self) -> Vec<(String, EndpointHealth)> {
+        self.ranked()
+            .into_iter()
+            .map(|endpoint| (endpoint.url.clone(), endpoint.health.lock().unwrap().clone()))
+            .collect()
+    }
+
+    /// Context slot of the last accepted snapshot.
+    pub fn last_slot(&self) -> Option<u64> {
+        Some(self.last_slot.load(Ordering::Acquire)).filter(|slot| *slot > 0)
+    }
+
+    /// Records a slot whose state came from elsewhere, such as a subscription, so responses from
+    /// before it are rejected too.
+    pub fn advance_slot(&self, slot: u64) {
+        self.last_slot.fetch_max(slot, Ordering::AcqRel);
+    }
+
+    /// Endpoints that last succeeded first, then by how far behind the highest slot they are,
+    /// then by latency.
+    fn ranked(&self) -> Vec<Arc<Endpoint>> {
+        let healths: Vec<EndpointHealth> =
+            self.endpoints.iter().map(|e| e.health.lock().unwrap().clone()).collect();
+        let max_slot = healths.iter().filter_map(|h| h.slot).max().unwrap_or_default();
+        let mut ranked: Vec<_> = self.endpoints.iter().zip(&healths).collect();
+        ranked.sort_by_key(|(_, health)| {
+            let lag = health.slot.map_or(u64::MAX, |slot| max_slot.saturating_sub(slot));
+            let lag = if lag <= SLOT_TOLERANCE { 0 } else { lag };
+            (health.failures > 0, lag, health.latency.unwrap_or(Duration::MAX))
+        });
+        ranked.into_iter().map(|(endpoint, _)| endpoint.clone()).collect()
+    }
+
+    /// Fetches `addresses` at processed commitment from the best endpoint that answers with a
+    /// slot no older than the last accepted one. Returns the context slot and the accounts.
+    pub async fn get_multiple_accounts(
+        &self,
+        addresses: &[Pubkey],
+    ) -> Result<(u64, Vec<Option<Account>>)> {
+        let mut errors = vec![];
+        for endpoint in self.ranked() {
+            let start = Instant::now();
+            let resp = match endpoint
+                .client
+                .get_multiple_accounts_with_commitment(addresses, CommitmentConfig::processed())
+                .await
+            {
+                Ok(resp) => resp,
+                Err(e) => {
+                    endpoint.failed();
+                    tracing::warn!("{} failed, trying the next endpoint: {e}", endpoint.url);
+                    errors.push(format!("{}: {e}", endpoint.url));
+                    continue;
+                }
+            };
+
+            let slot = resp.context.slot;
+            let last_slot = self.last_slot.load(Ordering::Acquire);
+            if slot < last_slot {
+                endpoint.failed();
+                tracing::warn!("{} answered at slot {slot}, behind slot {last_slot}", endpoint.url);
+                errors.push(format!("{}: slot {slot} is behind slot {last_slot}", endpoint.url));
+                continue;
+            }
+            endpoint.succeeded(slot, start.elapsed());
+            self.last_slot.fetch_max(slot, Ordering::AcqRel);
+            return Ok((slot, resp.value));
+        }
+        Err(Error::Rpc(errors.join("; ")))
+    }
+
+    /// Block time of `slot` from the first endpoint that has it.
+    pub async fn get_block_time(&self, slot: u64) -> Result<i64> {
+        let mut errors = vec![];
+        for endpoint in self.ranked() {
+            match endpoint.client.get_block_time(slot).await {
+                Ok(block_time) => return Ok(block_time),
+                Err(e) => errors.push(format!("{}: {e}", endpoint.url)),
+            }
+        }
+        Err(Error::Rpc(errors.join("; ")))
+    }
+
+    /// Latest blockhash from the best endpoint that answers.
+    pub async fn get_latest_blockhash(&self) -> Result<Hash> {
+        let mut errors = vec![];
+        for endpoint in self.ranked() {
+            match endpoint.client.get_latest_blockhash().await {
+                Ok(blockhash) => return Ok(blockhash),
+                Err(e) => {
+                    endpoint.failed();
+                    tracing::warn!("{} failed, trying the next endpoint: {e}", endpoint.url);
+                    errors.push(format!("{}: {e}", endpoint.url));
+                }
+            }
+        }
+        Err(Error::Rpc(errors.join("; ")))
+    }
+}
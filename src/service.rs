@@ -1,21 +1,66 @@
-use crate::cmd::{fetch_and_persist_accounts_with_client, simulate};
-use crate::swap::SwapDirection;
+use crate::cmd::{
+    ArbOpportunity, SpreadAnalysis, fetch_and_persist_accounts_with_pool, find_arbs,
+    geometric_sizes, local_block_time, persist_snapshot, spreads,
+};
+use crate::config;
+use axum::extract::rejection::QueryRejection;
+use axum::extract::{Path, Query};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
 use axum::{Router, http::StatusCode, response::Json, routing::post};
+use solana_pubkey::Pubkey;
+use solfi_sim::Venue;
+use solfi_sim::pubsub::{StreamOptions, stream_snapshots};
+use solfi_sim::rpc::RpcPool;
+use solfi_sim::swap::SwapDirection;
+use solfi_sim::{Simulator, SwapResult};
 
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
-use solana_rpc_client::nonblocking::rpc_client::RpcClient;
-use solana_sdk::commitment_config::CommitmentConfig;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use serde_json::{Value, json};
+use solfi_sim::types::FetchMetadata;
+use std::collections::BTreeMap;
+use std::sync::{Arc, LazyLock, Mutex, RwLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tokio::time::{interval, sleep};
+use utoipa::{IntoParams, OpenApi, ToSchema};
 
-pub async fn run_service(port: u16, rpc_url: String, fetch_interval_ms: u64) -> eyre::Result<()> {
-    tokio::spawn(async move { fetch_state_task(rpc_url, fetch_interval_ms).await });
+/// How often the fetcher re-ranks its RPC endpoints when nothing is failing.
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Most sizes one arb search may try. Each costs a swap per market and another per market pair.
+const MAX_ARB_STEPS: usize = 100;
+
+/// How long the subscribing fetcher polls after its WebSocket drops before reconnecting.
+const RESUBSCRIBE_AFTER: Duration = Duration::from_secs(10);
+
+/// Serves JSON-RPC and REST on `port` while keeping the snapshot fresh, by polling every
+/// `fetch_interval_ms` or, given a `ws_url`, by subscribing to the accounts there.
+pub async fn run_service(
+    port: u16,
+    rpc_urls: Vec<String>,
+    fetch_interval_ms: u64,
+    ws_url: Option<String>,
+) -> eyre::Result<()> {
+    let pool = Arc::new(RpcPool::new(&rpc_urls)?);
+    // Serve whatever the last run left behind until the first fetch
+    if let Err(e) = reload_snapshot() {
+        tracing::debug!("No snapshot to serve yet: {}", e);
+    }
+    let report = |fetched: eyre::Result<u64>| {
+        if let Err(e) = fetched.and_then(|_| reload_snapshot()) {
+            eprintln!("Failed to fetch accounts: {}", e);
+        }
+    };
+    match ws_url {
+        Some(ws_url) => tokio::spawn(subscribe_state_task(pool, ws_url, fetch_interval_ms, report)),
+        None => tokio::spawn(fetch_state_task(pool, fetch_interval_ms, report)),
+    };
 
     // Give the fetcher a moment to populate state
     sleep(Duration::from_millis(500)).await;
 
-    let app = Router::new().route("/", post(handle_jsonrpc));
+    let app = router();
 
     let addr = format!("0.0.0.0:{}", port);
     println!("Starting RPC server on {}", addr);
@@ -26,23 +71,88 @@ pub async fn run_service(port: u16, rpc_url: String, fetch_interval_ms: u64) ->
     Ok(())
 }
 
-async fn fetch_state_task(rpc_url: String, interval_ms: u64) {
-    let mut interval = interval(Duration::from_millis(interval_ms));
+/// Counters since the service started, served by `get_metrics`.
+#[derive(Clone, Default, Serialize, ToSchema)]
+struct Metrics {
+    requests: BTreeMap<String, u64>,
+    simulated_swaps: u64,
+    failed_swaps: u64,
+    /// Failed swaps by [`SimError::label`](solfi_sim::types::SimError::label)
+    errors: BTreeMap<String, u64>,
+}
 
-    let client = RpcClient::new_with_commitment(rpc_url, CommitmentConfig::confirmed());
+static METRICS: LazyLock<Mutex<Metrics>> = LazyLock::new(Default::default);
+
+fn record_swaps(results: &[SwapResult]) {
+    let mut metrics = METRICS.lock().unwrap();
+    metrics.simulated_swaps += results.len() as u64;
+    for error in results.iter().filter_map(|r| r.error.as_ref()) {
+        metrics.failed_swaps += 1;
+        *metrics.errors.entry(error.label()).or_default() += 1;
+    }
+}
+
+/// Refetches the snapshot every `interval_ms` and reports each attempt to `on_fetch`: the slot
+/// fetched, or why it failed.
+pub async fn fetch_state_task(
+    pool: Arc<RpcPool>,
+    interval_ms: u64,
+    mut on_fetch: impl FnMut(eyre::Result<u64>),
+) {
+    let mut interval = interval(Duration::from_millis(interval_ms));
+    let mut last_health_check: Option<Instant> = None;
 
     loop {
         interval.tick().await;
 
-        if let Err(e) = fetch_and_persist_accounts_with_client(&client).await {
-            eprintln!("Failed to fetch accounts: {}", e);
+        if last_health_check.is_none_or(|checked| checked.elapsed() >= HEALTH_CHECK_INTERVAL) {
+            pool.health_check().await;
+            last_health_check = Some(Instant::now());
+        }
+
+        // A getBlockTime per poll would double the requests and rarely succeeds for processed
+        // slots, so the service times snapshots by the local clock
+        let fetched = fetch_and_persist_accounts_with_pool(&pool, false).await;
+        if fetched.is_err() {
+            // Re-rank before the next tick rather than waiting out the interval
+            last_health_check = None;
         }
+        on_fetch(fetched);
     }
 }
 
+/// Same as [`fetch_state_task`], following the accounts over the PubSub API at `ws_url` and
+/// polling every `interval_ms` while that's down.
+pub async fn subscribe_state_task(
+    pool: Arc<RpcPool>,
+    ws_url: String,
+    interval_ms: u64,
+    mut on_fetch: impl FnMut(eyre::Result<u64>),
+) {
+    pool.health_check().await;
+    let options = StreamOptions {
+        poll_interval: Duration::from_millis(interval_ms),
+        resubscribe_after: RESUBSCRIBE_AFTER,
+    };
+    let addresses = config().solfi().accounts();
+    stream_snapshots(&pool, &ws_url, &addresses, options, |snapshot| {
+        let metadata = FetchMetadata::with_local_clock(snapshot.slot, local_block_time());
+        let persisted = persist_snapshot(&snapshot, &metadata);
+        on_fetch(
+            persisted.map(|()| snapshot.slot).map_err(|e| {
+                eyre::eyre!("failed to save snapshot at slot {}: {}", snapshot.slot, e)
+            }),
+        );
+    })
+    .await
+}
+
 #[derive(Deserialize)]
 struct JsonRpcRequest {
     method: String,
+    /// Named parameters, the same as the REST route's query
+    #[serde(default)]
+    params: Option<Value>,
     id: Option<Value>,
 }
 
@@ -64,132 +174,567 @@ struct JsonRpcError {
     data: Option<Value>,
 }
 
-#[derive(Serialize)]
+/// Why a request couldn't be answered, as a JSON-RPC error or an HTTP status.
+#[derive(Debug)]
+enum ApiError {
+    MethodNotFound,
+    InvalidParams(String),
+    /// A market that isn't configured
+    NotFound(String),
+    /// Nothing to answer from yet, such as before the first snapshot is fetched
+    Unavailable(String),
+    Internal(String),
+}
+
+impl ApiError {
+    fn code(&self) -> i32 {
+        match self {
+            ApiError::MethodNotFound => -32601,
+            ApiError::InvalidParams(_) | ApiError::NotFound(_) => -32602,
+            ApiError::Unavailable(_) => -32000,
+            ApiError::Internal(_) => -32603,
+        }
+    }
+
+    fn status(&self) -> StatusCode {
+        match self {
+            ApiError::MethodNotFound | ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::InvalidParams(_) => StatusCode::BAD_REQUEST,
+            ApiError::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn message(self) -> String {
+        match self {
+            ApiError::MethodNotFound => "Method not found".to_string(),
+            ApiError::InvalidParams(message)
+            | ApiError::NotFound(message)
+            | ApiError::Unavailable(message)
+            | ApiError::Internal(message) => message,
+        }
+    }
+}
+
+/// Body of a failed REST request.
+#[derive(Serialize, ToSchema)]
+struct ErrorBody {
+    error: String,
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.status(), Json(ErrorBody { error: self.message() })).into_response()
+    }
+}
+
+impl From<QueryRejection> for ApiError {
+    fn from(rejection: QueryRejection) -> Self {
+        ApiError::InvalidParams(rejection.body_text())
+    }
+}
+
+#[derive(Serialize, ToSchema)]
 struct PriceQuote {
     amount_sol: f64,
     price_usdc: f64,
     best_market: String,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 struct PricesResponse {
     sell_sol: Vec<PriceQuote>,
     buy_sol: Vec<PriceQuote>,
     timestamp: u64,
 }
 
+/// Direction of a swap, named as on the command line.
+#[derive(Clone, Copy, Default, Deserialize, ToSchema)]
+#[serde(rename_all = "kebab-case")]
+enum Direction {
+    #[default]
+    SolToUsdc,
+    UsdcToSol,
+}
+
+impl From<Direction> for SwapDirection {
+    fn from(direction: Direction) -> Self {
+        match direction {
+            Direction::SolToUsdc => SwapDirection::SolToUsdc,
+            Direction::UsdcToSol => SwapDirection::UsdcToSol,
+        }
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct QuoteParams {
+    /// Amount in: SOL selling, USDC buying
+    amount: f64,
+    #[serde(default)]
+    #[param(inline)]
+    direction: Direction,
+    /// Only quote this market (default: every configured market)
+    market: Option<String>,
+}
+
+/// Outcome of simulating one swap, see [`SwapResult`].
+#[derive(Serialize, ToSchema)]
+struct QuoteResult {
+    venue: String,
+    market: String,
+    in_amount: f64,
+    out_amount: Option<f64>,
+    /// USDC per SOL once network fees and rent are paid
+    net_price: Option<f64>,
+    compute_units: Option<u64>,
+    /// Why the swap failed, e.g. `solfi:0x12`
+    error: Option<String>,
+}
+
+impl From<SwapResult> for QuoteResult {
+    fn from(result: SwapResult) -> Self {
+        Self {
+            venue: result.venue,
+            market: result.market,
+            in_amount: result.in_amount,
+            out_amount: result.out_amount,
+            net_price: result.net_price,
+            compute_units: result.compute_units,
+            error: result.error.map(|e| e.label()),
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+struct MarketInfo {
+    address: String,
+    venue: String,
+}
+
+#[derive(Deserialize)]
+struct MarketParams {
+    market: String,
+}
+
+/// State a SolFi market account holds, read from the current snapshot.
+#[derive(Serialize, ToSchema)]
+struct MarketStateResponse {
+    address: String,
+    /// Approximate mid price in USDC per SOL
+    price: f64,
+    price_raw: u64,
+    generated_slot: u64,
+    /// Last slot the market quotes at
+    cutoff_slot: u64,
+    snapshot_slot: u64,
+    /// Slots from the snapshot to the cutoff, negative once past it
+    slots_until_cutoff: i64,
+}
+
+#[derive(Serialize, ToSchema)]
+struct SnapshotInfo {
+    slot: u64,
+    /// Unix timestamp (seconds) of the slot
+    block_time: Option<i64>,
+}
+
+/// Sizes searched for arbs, the same as the `arb` command's.
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct ArbsParams {
+    /// Smallest USDC amount to try
+    #[serde(default = "default_arb_min_usdc")]
+    #[param(default = 100.0)]
+    min_usdc: f64,
+    /// Largest USDC amount to try
+    #[serde(default = "default_arb_max_usdc")]
+    #[param(default = 100_000.0)]
+    max_usdc: f64,
+    /// Number of sizes to try, spaced geometrically between min and max
+    #[serde(default = "default_arb_steps")]
+    #[param(default = 12)]
+    steps: usize,
+}
+
+fn default_arb_min_usdc() -> f64 {
+    100.0
+}
+
+fn default_arb_max_usdc() -> f64 {
+    100_000.0
+}
+
+fn default_arb_steps() -> usize {
+    12
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct SpreadsParams {
+    /// USDC to start each market's round trip with
+    #[serde(default = "default_spread_usdc")]
+    #[param(default = 100.0)]
+    usdc: f64,
+}
+
+fn default_spread_usdc() -> f64 {
+    100.0
+}
+
 async fn handle_jsonrpc(
     Json(req): Json<JsonRpcRequest>,
 ) -> Result<Json<JsonRpcResponse>, StatusCode> {
-    let response = match req.method.as_str() {
-        "get_prices" => handle_get_prices(req.id).await,
-        _ => Json(JsonRpcResponse {
-            jsonrpc: "2.0".to_string(),
-            result: None,
-            error: Some(JsonRpcError {
-                code: -32601,
-                message: "Method not found".to_string(),
-                data: None,
-            }),
-            id: req.id,
-        }),
+    let params = req.params.unwrap_or_else(|| json!({}));
+    let method = req.method.clone();
+    let result = blocking(move || match method.as_str() {
+        "get_prices" => respond(get_prices()),
+        "get_arbs" => from_params(params).and_then(|params| respond(get_arbs(params))),
+        "get_metrics" => respond(Ok(get_metrics())),
+        "get_quote" => from_params(params).and_then(|params| respond(get_quote(params))),
+        "get_markets" => respond(get_markets()),
+        "get_market_state" => from_params(params)
+            .and_then(|params: MarketParams| respond(get_market_state(&params.market))),
+        "get_snapshot" => respond(get_snapshot()),
+        "get_spreads" => from_params(params).and_then(|params| respond(get_spreads(params))),
+        _ => Err(ApiError::MethodNotFound),
+    })
+    .await;
+    if !matches!(result, Err(ApiError::MethodNotFound)) {
+        count_request(&req.method);
+    }
+
+    let (result, error) = match result {
+        Ok(result) => (Some(result), None),
+        Err(e) => (None, Some(JsonRpcError { code: e.code(), message: e.message(), data: None })),
     };
+    Ok(Json(JsonRpcResponse { jsonrpc: "2.0".to_string(), result, error, id: req.id }))
+}
+
+/// Runs `f` on the blocking pool, as simulating swaps would hold up the async workers.
+async fn blocking<T: Send + 'static>(
+    f: impl FnOnce() -> Result<T, ApiError> + Send + 'static,
+) -> Result<T, ApiError> {
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| ApiError::Internal(format!("Request failed: {}", e)))?
+}
+
+fn respond<T: Serialize>(result: Result<T, ApiError>) -> Result<Value, ApiError> {
+    Ok(serde_json::to_value(result?).unwrap())
+}
+
+fn from_params<T: DeserializeOwned>(params: Value) -> Result<T, ApiError> {
+    serde_json::from_value(params)
+        .map_err(|e| ApiError::InvalidParams(format!("Invalid params: {}", e)))
+}
 
-    Ok(response)
+/// Counts a request under its JSON-RPC method, whichever way it came in.
+fn count_request(method: &str) {
+    *METRICS.lock().unwrap().requests.entry(method.to_string()).or_default() += 1;
 }
 
-async fn handle_get_prices(id: Option<Value>) -> Json<JsonRpcResponse> {
-    let amounts = vec![1.0, 10.0, 100.0];
+/// The snapshot requests are answered from, loaded once per fetch.
+struct ServedSnapshot {
+    simulator: Simulator,
+    metadata: FetchMetadata,
+}
+
+/// Replaced whole after every fetch, so each request quotes one consistent snapshot while the
+/// fetcher rewrites the data directory.
+static SNAPSHOT: RwLock<Option<Arc<ServedSnapshot>>> = RwLock::new(None);
+
+/// Loads the snapshot in the data directory and serves it from now on.
+fn reload_snapshot() -> eyre::Result<()> {
+    let simulator = Simulator::from_config(config(), None)?;
+    let metadata = FetchMetadata::read_from(&config().data_dir)
+        .ok_or_else(|| eyre::eyre!("no snapshot metadata in {}", config().data_dir.display()))?;
+    *SNAPSHOT.write().unwrap() = Some(Arc::new(ServedSnapshot { simulator, metadata }));
+    Ok(())
+}
+
+fn current_snapshot() -> Result<Arc<ServedSnapshot>, ApiError> {
+    SNAPSHOT
+        .read()
+        .unwrap()
+        .clone()
+        .ok_or_else(|| ApiError::Unavailable("No snapshot fetched yet".to_string()))
+}
+
+/// The configured market `id` names.
+fn find_market(simulator: &Simulator, id: &str) -> Result<Pubkey, ApiError> {
+    let market: Pubkey =
+        id.parse().map_err(|_| ApiError::InvalidParams(format!("Invalid market: {}", id)))?;
+    simulator
+        .markets()
+        .into_iter()
+        .any(|(_, m)| m == market)
+        .then_some(market)
+        .ok_or_else(|| ApiError::NotFound(format!("Unknown market: {}", id)))
+}
+
+fn get_prices() -> Result<PricesResponse, ApiError> {
+    let amounts = &config().service.ladder;
     let mut sell_sol_quotes = Vec::new();
     let mut buy_sol_quotes = Vec::new();
 
-    for amount in &amounts {
-        match simulate(SwapDirection::SolToUsdc, Some(*amount), None, false, false) {
-            Ok(results) => {
-                if let Some(best) = results
-                    .iter()
-                    .filter_map(|r| r.out_amount.map(|out| (r, out)))
-                    .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
-                {
-                    sell_sol_quotes.push(PriceQuote {
-                        amount_sol: *amount,
-                        price_usdc: best.1 / amount,
-                        best_market: best.0.market.clone(),
-                    });
-                }
-            }
-            Err(e) => {
-                return Json(JsonRpcResponse {
-                    jsonrpc: "2.0".to_string(),
-                    result: None,
-                    error: Some(JsonRpcError {
-                        code: -32603,
-                        message: format!("Failed to simulate sell: {}", e),
-                        data: None,
-                    }),
-                    id,
-                });
-            }
-        }
-    }
+    let snapshot = current_snapshot()?;
+    let simulator = &snapshot.simulator;
 
-    for target_sol in &amounts {
-        let initial_price =
-            if !sell_sol_quotes.is_empty() { sell_sol_quotes[0].price_usdc * 1.01 } else { 150.0 };
-
-        let mut estimated_usdc = target_sol * initial_price;
-        let mut best_result = None;
-        let mut iterations = 0;
-        const MAX_ITERATIONS: i32 = 10;
-        const TOLERANCE: f64 = 0.01;
-
-        while iterations < MAX_ITERATIONS {
-            match simulate(SwapDirection::UsdcToSol, Some(estimated_usdc), None, false, false) {
-                Ok(results) => {
-                    if let Some(best) = results
-                        .iter()
-                        .filter_map(|r| r.out_amount.map(|out| (r, out)))
-                        .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
-                    {
-                        let sol_received = best.1;
-                        let ratio = sol_received / target_sol;
-
-                        if (ratio - 1.0).abs() < TOLERANCE {
-                            best_result =
-                                Some((best.0.market.clone(), estimated_usdc, sol_received));
-                            break;
-                        }
-
-                        estimated_usdc = estimated_usdc / ratio;
-                        best_result = Some((best.0.market.clone(), estimated_usdc, sol_received));
-                    } else {
-                        break;
-                    }
-                }
-                Err(_) => break,
-            }
-            iterations += 1;
+    for amount in amounts {
+        let results = simulator
+            .quote_all(SwapDirection::SolToUsdc, *amount)
+            .map_err(|e| ApiError::Internal(format!("Failed to simulate sell: {}", e)))?;
+        record_swaps(&results);
+        if let Some(best) = results
+            .iter()
+            .filter_map(|r| r.out_amount.map(|out| (r, out)))
+            .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
+        {
+            sell_sol_quotes.push(PriceQuote {
+                amount_sol: *amount,
+                price_usdc: best.1 / amount,
+                best_market: best.0.market.clone(),
+            });
         }
+    }
 
-        if let Some((market, usdc_needed, sol_received)) = best_result {
+    let initial_price =
+        if !sell_sol_quotes.is_empty() { sell_sol_quotes[0].price_usdc * 1.01 } else { 150.0 };
+    for target_sol in amounts {
+        if let Some(quote) = simulator.search_buy(*target_sol, initial_price, record_swaps) {
             buy_sol_quotes.push(PriceQuote {
-                amount_sol: sol_received,
-                price_usdc: usdc_needed / sol_received,
-                best_market: market,
+                amount_sol: quote.sol_out,
+                price_usdc: quote.usdc_in / quote.sol_out,
+                best_market: quote.market,
             });
         }
     }
 
-    let response = PricesResponse {
+    Ok(PricesResponse {
         sell_sol: sell_sol_quotes,
         buy_sol: buy_sol_quotes,
         timestamp: SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs(),
-    };
+    })
+}
+
+fn get_arbs(params: ArbsParams) -> Result<Vec<ArbOpportunity>, ApiError> {
+    if !params.min_usdc.is_finite() || params.min_usdc <= 0.0 || params.max_usdc < params.min_usdc {
+        return Err(ApiError::InvalidParams("need 0 < min_usdc <= max_usdc".to_string()));
+    }
+    if params.steps == 0 || params.steps > MAX_ARB_STEPS {
+        return Err(ApiError::InvalidParams(format!("steps must be 1 to {}", MAX_ARB_STEPS)));
+    }
+    let snapshot = current_snapshot()?;
+    let simulator = &snapshot.simulator;
+    let sizes = geometric_sizes(params.min_usdc, params.max_usdc, params.steps);
+    find_arbs(simulator, &sizes, record_swaps)
+        .map_err(|e| ApiError::Internal(format!("Failed to search for arbs: {}", e)))
+}
+
+fn get_metrics() -> Metrics {
+    METRICS.lock().unwrap().clone()
+}
+
+fn get_quote(params: QuoteParams) -> Result<Vec<QuoteResult>, ApiError> {
+    if !params.amount.is_finite() || params.amount <= 0.0 {
+        return Err(ApiError::InvalidParams("amount must be positive".to_string()));
+    }
+    let snapshot = current_snapshot()?;
+    let simulator = &snapshot.simulator;
+    let direction = params.direction.into();
+    let results = match params.market {
+        Some(id) => {
+            let market = find_market(simulator, &id)?;
+            simulator
+                .simulate_swap(&market, direction, params.amount, &Default::default())
+                .map(|result| vec![result])
+        }
+        None => simulator.quote_all(direction, params.amount),
+    }
+    .map_err(|e| ApiError::Internal(format!("Failed to simulate swap: {}", e)))?;
+    record_swaps(&results);
+    Ok(results.into_iter().map(QuoteResult::from).collect())
+}
+
+fn get_markets() -> Result<Vec<MarketInfo>, ApiError> {
+    let snapshot = current_snapshot()?;
+    let simulator = &snapshot.simulator;
+    Ok(simulator
+        .markets()
+        .into_iter()
+        .map(|(venue, market)| MarketInfo {
+            address: market.to_string(),
+            venue: venue.name().to_string(),
+        })
+        .collect())
+}
 
-    Json(JsonRpcResponse {
-        jsonrpc: "2.0".to_string(),
-        result: Some(serde_json::to_value(response).unwrap()),
-        error: None,
-        id,
+fn get_market_state(id: &str) -> Result<MarketStateResponse, ApiError> {
+    let snapshot = current_snapshot()?;
+    let simulator = &snapshot.simulator;
+    let market = find_market(simulator, id)?;
+    let state = simulator
+        .market_state(&market)
+        .map_err(|e| ApiError::NotFound(format!("No SolFi state for {}: {}", id, e)))?;
+    let snapshot_slot = simulator.slot();
+    Ok(MarketStateResponse {
+        address: market.to_string(),
+        price: state.price(),
+        price_raw: state.price_raw,
+        generated_slot: state.generated_slot,
+        cutoff_slot: state.cutoff_slot,
+        snapshot_slot,
+        slots_until_cutoff: state.cutoff_slot as i64 - snapshot_slot as i64,
     })
 }
+
+fn get_snapshot() -> Result<SnapshotInfo, ApiError> {
+    let metadata = &current_snapshot()?.metadata;
+    Ok(SnapshotInfo { slot: metadata.slot(), block_time: metadata.block_time() })
+}
+
+fn get_spreads(params: SpreadsParams) -> Result<Vec<SpreadAnalysis>, ApiError> {
+    if !params.usdc.is_finite() || params.usdc <= 0.0 {
+        return Err(ApiError::InvalidParams("usdc must be positive".to_string()));
+    }
+    spreads(&current_snapshot()?.simulator, params.usdc)
+        .map_err(|e| ApiError::Internal(format!("Failed to calculate spreads: {}", e)))
+}
+
+/// Best price for each size of the configured ladder, selling and buying SOL.
+#[utoipa::path(get, path = "/prices", responses(
+    (status = 200, body = PricesResponse),
+    (status = 500, body = ErrorBody),
+    (status = 503, description = "No snapshot fetched yet", body = ErrorBody),
+))]
+async fn rest_prices() -> Result<Json<PricesResponse>, ApiError> {
+    count_request("get_prices");
+    Ok(Json(blocking(get_prices).await?))
+}
+
+/// Profitable round trips between two markets, best first.
+#[utoipa::path(get, path = "/arbs", params(ArbsParams), responses(
+    (status = 200, body = [ArbOpportunity]),
+    (status = 400, body = ErrorBody),
+    (status = 500, body = ErrorBody),
+    (status = 503, description = "No snapshot fetched yet", body = ErrorBody),
+))]
+async fn rest_arbs(
+    params: Result<Query<ArbsParams>, QueryRejection>,
+) -> Result<Json<Vec<ArbOpportunity>>, ApiError> {
+    count_request("get_arbs");
+    let params = params?.0;
+    Ok(Json(blocking(move || get_arbs(params)).await?))
+}
+
+/// Request and swap counters since the service started.
+#[utoipa::path(get, path = "/metrics", responses((status = 200, body = Metrics)))]
+async fn rest_metrics() -> Json<Metrics> {
+    count_request("get_metrics");
+    Json(get_metrics())
+}
+
+/// Simulates a swap on one market or all of them.
+#[utoipa::path(get, path = "/quote", params(QuoteParams), responses(
+    (status = 200, body = [QuoteResult]),
+    (status = 400, body = ErrorBody),
+    (status = 404, description = "The market isn't configured", body = ErrorBody),
+    (status = 500, body = ErrorBody),
+    (status = 503, description = "No snapshot fetched yet", body = ErrorBody),
+))]
+async fn rest_quote(
+    params: Result<Query<QuoteParams>, QueryRejection>,
+) -> Result<Json<Vec<QuoteResult>>, ApiError> {
+    count_request("get_quote");
+    let params = params?.0;
+    Ok(Json(blocking(move || get_quote(params)).await?))
+}
+
+/// Configured markets and the venue trading each.
+#[utoipa::path(get, path = "/markets", responses(
+    (status = 200, body = [MarketInfo]),
+    (status = 503, description = "No snapshot fetched yet", body = ErrorBody),
+))]
+async fn rest_markets() -> Result<Json<Vec<MarketInfo>>, ApiError> {
+    count_request("get_markets");
+    Ok(Json(blocking(get_markets).await?))
+}
+
+/// Price and cutoff a SolFi market holds in the current snapshot.
+#[utoipa::path(
+    get,
+    path = "/markets/{id}/state",
+    params(("id" = String, Path, description = "Market address")),
+    responses(
+        (status = 200, body = MarketStateResponse),
+        (status = 400, body = ErrorBody),
+        (status = 404, description = "The market isn't configured", body = ErrorBody),
+        (status = 503, description = "No snapshot fetched yet", body = ErrorBody),
+    ),
+)]
+async fn rest_market_state(Path(id): Path<String>) -> Result<Json<MarketStateResponse>, ApiError> {
+    count_request("get_market_state");
+    Ok(Json(blocking(move || get_market_state(&id)).await?))
+}
+
+/// Slot and block time of the snapshot being quoted.
+#[utoipa::path(get, path = "/snapshot", responses(
+    (status = 200, body = SnapshotInfo),
+    (status = 503, description = "No snapshot fetched yet", body = ErrorBody),
+))]
+async fn rest_snapshot() -> Result<Json<SnapshotInfo>, ApiError> {
+    count_request("get_snapshot");
+    Ok(Json(blocking(get_snapshot).await?))
+}
+
+/// Bid-ask spread of each market from a round trip within it, tightest first.
+#[utoipa::path(get, path = "/spreads", params(SpreadsParams), responses(
+    (status = 200, body = [SpreadAnalysis]),
+    (status = 400, body = ErrorBody),
+    (status = 500, body = ErrorBody),
+    (status = 503, description = "No snapshot fetched yet", body = ErrorBody),
+))]
+async fn rest_spreads(
+    params: Result<Query<SpreadsParams>, QueryRejection>,
+) -> Result<Json<Vec<SpreadAnalysis>>, ApiError> {
+    count_request("get_spreads");
+    let params = params?.0;
+    Ok(Json(blocking(move || get_spreads(params)).await?))
+}
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "solfi-sim",
+        description = "Quotes simulated on the latest snapshot of the SolFi markets. Every route \
+                       returns the same data as the JSON-RPC method at `POST /` named `get_` and \
+                       the route, with query parameters as named params."
+    ),
+    paths(
+        rest_prices,
+        rest_arbs,
+        rest_metrics,
+        rest_quote,
+        rest_markets,
+        rest_market_state,
+        rest_snapshot,
+        rest_spreads
+    )
+)]
+struct ApiDoc;
+
+/// JSON-RPC at `/`, the same methods as REST routes, and their OpenAPI document.
+fn router() -> Router {
+    Router::new()
+        .route("/", post(handle_jsonrpc))
+        .route("/prices", get(rest_prices))
+        .route("/arbs", get(rest_arbs))
+        .route("/metrics", get(rest_metrics))
+        .route("/quote", get(rest_quote))
+        .route("/markets", get(rest_markets))
+        .route("/markets/{id}/state", get(rest_market_state))
+        .route("/snapshot", get(rest_snapshot))
+        .route("/spreads", get(rest_spreads))
+        .route("/openapi.json", get(|| async { Json(ApiDoc::openapi()) }))
+}
@@ -0,0 +1,676 @@
+use crate::config::Config;
+use crate::constants::{SOL_DECIMALS, SOLFI_PROGRAM, USDC, USDC_DECIMALS, WSOL};
+use crate::error::{Error, Result};
+use crate::market::MarketState;
+use crate::reference::{ReferenceKind, ReferenceModel, ReferenceParams};
+use crate::swap::{SwapDirection, create_swap_ix};
+use crate::types::{AccountWithAddress, FetchMetadata, SimError};
+use crate::utils::token_balance;
+use crate::venue::{Solfi, Venue};
+use litesvm::LiteSVM;
+use serde::Serialize;
+use solana_account::Account;
+use solana_compute_budget::compute_budget_limits::{
+    DEFAULT_INSTRUCTION_COMPUTE_UNIT_LIMIT, MAX_COMPUTE_UNIT_LIMIT,
+};
+use solana_keypair::Keypair;
+use solana_pubkey::Pubkey;
+use solana_sdk::compute_budget::ComputeBudgetInstruction;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::native_token::sol_to_lamports;
+use solana_sdk::program_pack::Pack;
+use solana_sdk::rent::Rent;
+use solana_sdk::rent_collector::RENT_EXEMPT_RENT_EPOCH;
+use solana_signer::Signer;
+use solana_system_interface::instruction::transfer;
+use solana_transaction::Transaction;
+use spl_associated_token_account::get_associated_token_address;
+use spl_associated_token_account::instruction::create_associated_token_account_idempotent;
+use spl_token::instruction::sync_native;
+use spl_token::state::{Account as TokenAccount, AccountState};
+use std::path::Path;
+use std::sync::Arc;
+
+const MICRO_LAMPORTS_PER_LAMPORT: u128 = 1_000_000;
+
+fn mk_ata_account(mint: &Pubkey, user: &Pubkey, amount: u64) -> Account {
+    let ata = TokenAccount {
+        mint: *mint,
+        owner: *user,
+        amount,
+        state: AccountState::Initialized,
+        ..Default::default()
+    };
+    let mut data = vec![0u8; TokenAccount::LEN];
+    ata.pack_into_slice(&mut data);
+    Account {
+        lamports: Rent::default().minimum_balance(data.len()),
+        data,
+        owner: spl_token::id(),
+        executable: false,
+        rent_epoch: RENT_EXEMPT_RENT_EPOCH,
+    }
+}
+
+/// Decimals of the input and output mints of a swap in `direction`.
+fn decimals(direction: SwapDirection) -> (i32, i32) {
+    match direction {
+        SwapDirection::SolToUsdc => (SOL_DECIMALS, USDC_DECIMALS),
+        SwapDirection::UsdcToSol => (USDC_DECIMALS, SOL_DECIMALS),
+    }
+}
+
+/// Converts a UI amount of the input mint for `direction` into atomic units.
+pub fn to_atomic(direction: SwapDirection, amount: f64) -> u64 {
+    (amount * 10f64.powi(decimals(direction).0)) as u64
+}
+
+/// The user's token account that receives the output of a swap in `direction`.
+fn output_ata(user: &Pubkey, direction: SwapDirection) -> Pubkey {
+    match direction {
+        SwapDirection::SolToUsdc => get_associated_token_address(user, &USDC),
+        SwapDirection::UsdcToSol => get_associated_token_address(user, &WSOL),
+    }
+}
+
+/// Creates an SVM holding the snapshot in `data_dir`, warped to `slot` (default: the slot the
+/// snapshot was fetched at). The SolFi program itself is left for the caller to load.
+pub fn load_snapshot_svm(data_dir: &Path, slot: Option<u64>) -> Result<LiteSVM> {
+    let mut svm =
+        LiteSVM::new().with_sysvars().with_precompiles().with_sigverify(true).with_spl_programs();
+
+    for acct in AccountWithAddress::read_all_from(data_dir)? {
+        svm.set_account(acct.address, acct.account)?;
+    }
+    if let Some(slot) = slot.or(FetchMetadata::read_from(data_dir).map(|m| m.slot())) {
+        svm.warp_to_slot(slot);
+    }
+    Ok(svm)
+}
+
+/// Gives `user` `total_amount_in` atomic units of the input mint for `direction`, plus SOL for
+/// fees.
+pub fn fund_user(
+    svm: &mut LiteSVM,
+    user: &Pubkey,
+    direction: SwapDirection,
+    total_amount_in: u64,
+) -> Result<()> {
+    let fee_lamports = sol_to_lamports(1.0);
+    let airdrop = |svm: &mut LiteSVM, lamports| {
+        svm.airdrop(user, lamports)
+            .map(|_| ())
+            .map_err(|e| Error::Svm(format!("failed to airdrop SOL: {}", e.err)))
+    };
+    match direction {
+        SwapDirection::SolToUsdc => airdrop(svm, total_amount_in + fee_lamports)?,
+        SwapDirection::UsdcToSol => {
+            airdrop(svm, fee_lamports)?;
+            let usdc_ata = get_associated_token_address(user, &USDC);
+            let usdc_account = mk_ata_account(&USDC, user, total_amount_in);
+            svm.set_account(usdc_ata, usdc_account)?;
+        }
+    }
+    Ok(())
+}
+
+/// The instructions for a single swap: create both user ATAs, wrap the SOL being sold, then swap.
+pub fn swap_instructions(
+    direction: SwapDirection,
+    market: &Pubkey,
+    user: &Pubkey,
+    amount_in_atomic: u64,
+) -> Result<Vec<Instruction>> {
+    let mut instructions = token_setup_instructions(direction, user, amount_in_atomic)?;
+    instructions.push(create_swap_ix(direction, market, user, &WSOL, &USDC, amount_in_atomic));
+    Ok(instructions)
+}
+
+/// Creates both user ATAs and wraps the SOL being sold, ready for any venue's swap instruction.
+pub fn token_setup_instructions(
+    direction: SwapDirection,
+    user: &Pubkey,
+    amount_in_atomic: u64,
+) -> Result<Vec<Instruction>> {
+    let wsol_ata = get_associated_token_address(user, &WSOL);
+    let mut instructions = vec![
+        create_associated_token_account_idempotent(user, user, &WSOL, &spl_token::id()),
+        create_associated_token_account_idempotent(user, user, &USDC, &spl_token::id()),
+    ];
+
+    if direction == SwapDirection::SolToUsdc {
+        let sync = sync_native(&spl_token::id(), &wsol_ata)
+            .map_err(|e| Error::InvalidInput(e.to_string()))?;
+        instructions.extend([transfer(user, &wsol_ata, amount_in_atomic), sync]);
+    }
+    Ok(instructions)
+}
+
+/// Compute budget instructions to put in front of a swap.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ComputeBudgetOptions {
+    /// Compute unit limit to request. Without it the runtime default applies
+    pub unit_limit: Option<u32>,
+    /// Priority fee in micro-lamports per compute unit
+    pub unit_price: Option<u64>,
+}
+
+impl ComputeBudgetOptions {
+    pub fn instructions(&self) -> Vec<Instruction> {
+        let mut instructions = vec![];
+        if let Some(limit) = self.unit_limit {
+            instructions.push(ComputeBudgetInstruction::set_compute_unit_limit(limit));
+        }
+        if let Some(price) = self.unit_price {
+            instructions.push(ComputeBudgetInstruction::set_compute_unit_price(price));
+        }
+        instructions
+    }
+
+    /// The limit the runtime applies to a transaction with `other_instructions` instructions
+    /// besides the compute budget ones: the requested one, or 200k per instruction up to 1.4M.
+    pub fn effective_limit(&self, other_instructions: usize) -> u32 {
+        self.unit_limit
+            .unwrap_or(
+                (DEFAULT_INSTRUCTION_COMPUTE_UNIT_LIMIT as usize * other_instructions)
+                    .min(MAX_COMPUTE_UNIT_LIMIT as usize) as u32,
+            )
+            .min(MAX_COMPUTE_UNIT_LIMIT)
+    }
+
+    /// Priority fee in lamports. It's charged on the limit, not on the units actually used.
+    pub fn priority_fee(&self, other_instructions: usize) -> u64 {
+        let micro_lamports = self.unit_price.unwrap_or_default() as u128
+            * self.effective_limit(other_instructions) as u128;
+        micro_lamports.div_ceil(MICRO_LAMPORTS_PER_LAMPORT) as u64
+    }
+}
+
+/// Result of [`Simulator::search_buy`].
+#[derive(Clone, Debug, Serialize)]
+pub struct BuyQuote {
+    pub market: String,
+    /// USDC swapped in, in UI units
+    pub usdc_in: f64,
+    /// SOL received, in UI units
+    pub sol_out: f64,
+}
+
+/// What a swap costs the user on top of the amount swapped, in lamports.
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+pub struct SwapCosts {
+    /// Transaction fee paid by the fee payer
+    pub network_fee: u64,
+    /// Rent locked in the user's USDC account, if the transaction created it
+    pub ata_rent: u64,
+    /// Rent locked in the user's WSOL account used to wrap or receive SOL, if the transaction
+    /// created it. Both rents come back when the accounts are closed
+    pub wsol_rent: u64,
+    /// Priority fee for the requested compute unit price. LiteSVM doesn't charge it, so it's
+    /// computed from the compute budget rather than measured
+    pub priority_fee: u64,
+}
+
+impl SwapCosts {
+    pub fn total(&self) -> u64 {
+        self.network_fee + self.ata_rent + self.wsol_rent + self.priority_fee
+    }
+}
+
+/// Outcome of simulating one swap on one market.
+#[derive(Clone, Debug, Serialize)]
+pub struct SwapResult {
+    /// [`Venue::name`] of the market's venue
+    pub venue: String,
+    pub market: String,
+    pub in_amount: f64,
+    pub out_amount: Option<f64>,
+    pub error: Option<SimError>,
+    /// Only known for successful swaps
+    pub costs: Option<SwapCosts>,
+    /// USDC per SOL once [`SwapCosts`] are paid: costs are added to the SOL sold or taken from
+    /// the SOL bought
+    pub net_price: Option<f64>,
+    /// Compute units the transaction consumed, for successful swaps
+    pub compute_units: Option<u64>,
+    /// Compute unit limit the transaction ran with; `None` for results that aren't simulated
+    /// transactions
+    pub compute_unit_limit: Option<u32>,
+}
+
+/// Lamports held by `address`, zero if it doesn't exist.
+fn lamports(svm: &LiteSVM, address: &Pubkey) -> u64 {
+    svm.get_account(address).map_or(0, |a| a.lamports)
+}
+
+/// Swaps `amount` (UI units) on `venue`'s `market` as `user_keypair` behind the `budget`
+/// instructions and
+/// accounts for the output and every lamport the user spent on the way.
+fn run_swap(
+    svm: &mut LiteSVM,
+    user_keypair: &Keypair,
+    venue: &dyn Venue,
+    market: &Pubkey,
+    direction: SwapDirection,
+    amount: f64,
+    budget: &ComputeBudgetOptions,
+) -> Result<SwapResult> {
+    let user = user_keypair.pubkey();
+    let amount_in_atomic = to_atomic(direction, amount);
+    let mut swap = token_setup_instructions(direction, &user, amount_in_atomic)?;
+    swap.push(venue.swap_ix(market, &user, direction, amount_in_atomic)?);
+    let instructions = [budget.instructions(), swap.clone()].concat();
+    let (wsol_ata, usdc_ata) =
+        (get_associated_token_address(&user, &WSOL), get_associated_token_address(&user, &USDC));
+    let to_ata = output_ata(&user, direction);
+    let to_decimals = decimals(direction).1;
+    let (sol_before, out_before) = (lamports(svm, &user), token_balance(svm, &to_ata));
+    let (wsol_before, usdc_before) = (lamports(svm, &wsol_ata), lamports(svm, &usdc_ata));
+
+    let tx = Transaction::new_with_payer(&instructions, Some(&user));
+    let signed_tx = Transaction::new(&[user_keypair], tx.message, svm.latest_blockhash());
+    let mut result = SwapResult {
+        venue: venue.name().to_string(),
+        market: market.to_string(),
+        in_amount: amount,
+        out_amount: None,
+        error: None,
+        costs: None,
+        net_price: None,
+        compute_units: None,
+        compute_unit_limit: Some(budget.effective_limit(swap.len())),
+    };
+    let meta = match svm.send_transaction(signed_tx) {
+        Ok(meta) => meta,
+        Err(err) => {
+            result.error = Some(SimError::from_failed_tx(&err, &instructions));
+            return Ok(result);
+        }
+    };
+
+    let increase = |what: &str, before: u64, after: u64| {
+        after.checked_sub(before).ok_or_else(|| {
+            Error::BalanceChange(format!("{what} fell from {before} to {after} on {market}"))
+        })
+    };
+    let decrease = |what: &str, before: u64, after: u64| {
+        before.checked_sub(after).ok_or_else(|| {
+            Error::BalanceChange(format!("{what} rose from {before} to {after} on {market}"))
+        })
+    };
+    let out_atomic = increase("output token balance", out_before, token_balance(svm, &to_ata))?;
+    // The fee is whatever left the wallet that isn't the SOL sold or now sitting in the token
+    // accounts (wrapped SOL bought, or rent).
+    let (sol_in, sol_out) = match direction {
+        SwapDirection::SolToUsdc => (to_atomic(direction, amount), 0),
+        SwapDirection::UsdcToSol => (0, out_atomic),
+    };
+    let (wsol_after, usdc_after) = (lamports(svm, &wsol_ata), lamports(svm, &usdc_ata));
+    let spent = decrease("wallet lamports", sol_before, lamports(svm, &user))?;
+    let into_atas =
+        increase("token account lamports", wsol_before + usdc_before, wsol_after + usdc_after)?;
+    let rent = svm.minimum_balance_for_rent_exemption(TokenAccount::LEN);
+    let costs = SwapCosts {
+        network_fee: (spent + sol_out).saturating_sub(into_atas + sol_in),
+        ata_rent: if usdc_before == 0 && usdc_after > 0 { rent } else { 0 },
+        wsol_rent: if wsol_before == 0 && wsol_after > 0 { rent } else { 0 },
+        priority_fee: budget.priority_fee(swap.len()),
+    };
+
+    let out_amount = out_atomic as f64 / 10f64.powi(to_decimals);
+    let cost_sol = costs.total() as f64 / 10f64.powi(SOL_DECIMALS);
+    let net_price = match direction {
+        SwapDirection::SolToUsdc => out_amount / (amount + cost_sol),
+        SwapDirection::UsdcToSol => amount / (out_amount - cost_sol),
+    };
+    result.out_amount = Some(out_amount);
+    result.costs = Some(costs);
+    result.net_price = (net_price.is_finite() && net_price > 0.0).then_some(net_price);
+    result.compute_units = Some(meta.compute_units_consumed);
+    Ok(result)
+}
+
+/// A snapshot loaded into an SVM together with the SolFi program and any other [`Venue`]s, ready
+/// to simulate swaps on. Every simulation runs on a copy, so the loaded state never changes.
+#[derive(Clone)]
+pub struct Simulator {
+    svm: LiteSVM,
+    slot: u64,
+    /// Wallet every swap is simulated for. Compute usage depends on it (deriving its token
+    /// account addresses takes a varying number of hashes), so it's kept for the simulator's
+    /// lifetime to make compute unit measurements repeatable
+    user_keypair: Arc<Keypair>,
+    venues: Vec<Arc<dyn Venue>>,
+}
+
+impl Simulator {
+    /// Loads the snapshot in `data_dir` at the slot it was fetched at. SolFi's markets and program
+    /// come from the config file in the working directory, see [`Config::discover`].
+    pub fn load(data_dir: &Path) -> Result<Self> {
+        Self::load_at(data_dir, None)
+    }
+
+    /// Same as [`Simulator::load`] but warped to `slot` when given.
+    pub fn load_at(data_dir: &Path, slot: Option<u64>) -> Result<Self> {
+        Self::load_with(data_dir, slot, Config::discover()?.solfi())
+    }
+
+    /// Loads the snapshot in the configured data directory, quoting the configured markets.
+    pub fn from_config(config: &Config, slot: Option<u64>) -> Result<Self> {
+        Self::load_with(&config.data_dir, slot, config.solfi())
+    }
+
+    /// Same as [`Simulator::load_at`] with SolFi's markets and program taken from `solfi`.
+    pub fn load_with(data_dir: &Path, slot: Option<u64>, solfi: Solfi) -> Result<Self> {
+        let mut svm = load_snapshot_svm(data_dir, slot)?;
+        svm.add_program_from_file(SOLFI_PROGRAM, solfi.program_path())?;
+        let slot = svm.get_sysvar::<solana_sdk::clock::Clock>().slot;
+        let user_keypair = Arc::new(Keypair::new());
+        Ok(Self { svm, slot, user_keypair, venues: vec![Arc::new(solfi)] })
+    }
+
+    /// Loads another venue's program and account snapshot so its markets are quoted alongside
+    /// SolFi's. Accounts from the venue's snapshot replace any with the same address.
+    pub fn add_venue(&mut self, venue: Arc<dyn Venue>) -> Result<()> {
+        if let Some(dir) = venue.snapshot_dir() {
+            for acct in AccountWithAddress::read_all_from(dir)? {
+                self.svm.set_account(acct.address, acct.account)?;
+            }
+        }
+        for market in venue.markets() {
+            if self.svm.get_account(&market).is_none() {
+                return Err(Error::MissingAccount(market));
+            }
+        }
+        self.svm.add_program_from_file(venue.program_id(), venue.program_path())?;
+        self.venues.push(venue);
+        Ok(())
+    }
+
+    pub fn venues(&self) -> &[Arc<dyn Venue>] {
+        &self.venues
+    }
+
+    /// Every quotable market with its venue.
+    pub fn markets(&self) -> Vec<(&dyn Venue, Pubkey)> {
+        self.venues
+            .iter()
+            .flat_map(|venue| venue.markets().into_iter().map(|market| (venue.as_ref(), market)))
+            .collect()
+    }
+
+    fn venue_of(&self, market: &Pubkey) -> Result<&dyn Venue> {
+        self.venues
+            .iter()
+            .find(|venue| venue.markets().contains(market))
+            .map(|venue| venue.as_ref())
+            .ok_or_else(|| Error::InvalidInput(format!("{market} isn't a market of any venue")))
+    }
+
+    /// Slot the simulations run at.
+    pub fn slot(&self) -> u64 {
+        self.slot
+    }
+
+    /// The loaded SVM, for running custom transactions on a clone.
+    pub fn svm(&self) -> &LiteSVM {
+        &self.svm
+    }
+
+    /// Decodes the state of `market` as stored in the snapshot.
+    pub fn market_state(&self, market: &Pubkey) -> Result<MarketState> {
+        let account = self.svm.get_account(market).ok_or(Error::MissingAccount(*market))?;
+        MarketState::decode(*market, &account.data)
+    }
+
+    /// A reference pool with the balances of SolFi `market`'s vaults, priced around its mid price.
+    pub fn reference_model(
+        &self,
+        market: &Pubkey,
+        kind: ReferenceKind,
+        params: ReferenceParams,
+    ) -> Result<ReferenceModel> {
+        let balance = |mint: &Pubkey, decimals: i32| {
+            let vault = get_associated_token_address(market, mint);
+            token_balance(&self.svm, &vault) as f64 / 10f64.powi(decimals)
+        };
+        Ok(ReferenceModel::new(
+            kind,
+            balance(&WSOL, SOL_DECIMALS),
+            balance(&USDC, USDC_DECIMALS),
+            self.market_state(market)?.price(),
+            params,
+        ))
+    }
+
+    /// A funded user ready to swap `amount_in_atomic` on `market`, see [`SwapHarness`].
+    pub fn harness(
+        &self,
+        market: Pubkey,
+        direction: SwapDirection,
+        amount_in_atomic: u64,
+    ) -> Result<SwapHarness> {
+        let mut svm = self.svm.clone();
+        let user_keypair = self.user_keypair.clone();
+        fund_user(&mut svm, &user_keypair.pubkey(), direction, amount_in_atomic)?;
+        Ok(SwapHarness { svm, user_keypair, market, direction, amount_in_atomic })
+    }
+
+    /// Output (UI units) of swapping `amount` (UI units) on `market`.
+    pub fn quote(&self, market: &Pubkey, direction: SwapDirection, amount: f64) -> Result<f64> {
+        let result = self.simulate_swap(market, direction, amount, &Default::default())?;
+        match (result.out_amount, result.error) {
+            (Some(out), _) => Ok(out),
+            (None, Some(error)) => Err(Error::Swap(error)),
+            (None, None) => unreachable!("a swap either pays out or fails"),
+        }
+    }
+
+    /// Simulates swapping `amount` (UI units) on every market of every venue, each from the same
+    /// starting state. Failed swaps are returned with their error rather than failing the whole
+    /// call.
+    pub fn quote_all(&self, direction: SwapDirection, amount: f64) -> Result<Vec<SwapResult>> {
+        self.quote_all_with(direction, amount, &ComputeBudgetOptions::default())
+    }
+
+    /// Same as [`Simulator::quote_all`] with compute budget instructions in front of each swap.
+    pub fn quote_all_with(
+        &self,
+        direction: SwapDirection,
+        amount: f64,
+        budget: &ComputeBudgetOptions,
+    ) -> Result<Vec<SwapResult>> {
+        self.markets()
+            .into_iter()
+            .map(|(venue, market)| self.simulate_on(venue, &market, direction, amount, budget))
+            .collect()
+    }
+
+    /// Simulates swapping `amount` (UI units) on `market` as a freshly funded user.
+    pub fn simulate_swap(
+        &self,
+        market: &Pubkey,
+        direction: SwapDirection,
+        amount: f64,
+        budget: &ComputeBudgetOptions,
+    ) -> Result<SwapResult> {
+        self.simulate_on(self.venue_of(market)?, market, direction, amount, budget)
+    }
+
+    /// Same as [`Simulator::simulate_swap`] on a given venue, for markets more than one venue
+    /// can trade.
+    pub fn simulate_on(
+        &self,
+        venue: &dyn Venue,
+        market: &Pubkey,
+        direction: SwapDirection,
+        amount: f64,
+        budget: &ComputeBudgetOptions,
+    ) -> Result<SwapResult> {
+        let mut svm = self.svm.clone();
+        let user = self.user_keypair.pubkey();
+        fund_user(&mut svm, &user, direction, to_atomic(direction, amount))?;
+        run_swap(&mut svm, &self.user_keypair, venue, market, direction, amount, budget)
+    }
+
+    /// Smallest compute unit limit the swap succeeds with, found by bisection, or `None` if it
+    /// fails even at the maximum limit. The compute unit price instruction, if `unit_price` is
+    /// set, costs units too. Only exact for this simulator's wallet; other wallets may need a few
+    /// thousand units more or less.
+    pub fn min_compute_unit_limit(
+        &self,
+        venue: &dyn Venue,
+        market: &Pubkey,
+        direction: SwapDirection,
+        amount: f64,
+        unit_price: Option<u64>,
+    ) -> Result<Option<u32>> {
+        let succeeds = |limit| -> Result<Option<u64>> {
+            let budget = ComputeBudgetOptions { unit_limit: Some(limit), unit_price };
+            Ok(self.simulate_on(venue, market, direction, amount, &budget)?.compute_units)
+        };
+        let Some(consumed) = succeeds(MAX_COMPUTE_UNIT_LIMIT)? else {
+            return Ok(None);
+        };
+        // Usually the units consumed are exactly enough, but don't rely on it.
+        let (mut low, mut high) = (0, consumed as u32);
+        if succeeds(high)?.is_none() {
+            high = MAX_COMPUTE_UNIT_LIMIT;
+        }
+        while high - low > 1 {
+            let mid = low + (high - low) / 2;
+            if succeeds(mid)?.is_some() {
+                high = mid;
+            } else {
+                low = mid;
+            }
+        }
+        Ok(Some(high))
+    }
+
+    /// Searches for the USDC that buys `target_sol` on the best market. Starts from
+    /// `initial_price` and rescales the input by how far each quote lands from the target, until
+    /// one is within 1% or after 10 tries. Without convergence it returns the last quote's market
+    /// and output with the rescaled input. `None` if the first quote fails on every market. Every
+    /// batch of quotes it simulates is passed to `on_quotes`.
+    pub fn search_buy(
+        &self,
+        target_sol: f64,
+        initial_price: f64,
+        mut on_quotes: impl FnMut(&[SwapResult]),
+    ) -> Option<BuyQuote> {
+        const MAX_ITERATIONS: usize = 10;
+        const TOLERANCE: f64 = 0.01;
+
+        let mut usdc_in = target_sol * initial_price;
+        let mut best = None;
+        for _ in 0..MAX_ITERATIONS {
+            let Ok(results) = self.quote_all(SwapDirection::UsdcToSol, usdc_in) else {
+                break;
+            };
+            on_quotes(&results);
+            let Some((result, sol_out)) = results
+                .iter()
+                .filter_map(|r| r.out_amount.map(|out| (r, out)))
+                .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            else {
+                break;
+            };
+            let ratio = sol_out / target_sol;
+            if (ratio - 1.0).abs() < TOLERANCE {
+                return Some(BuyQuote { market: result.market.clone(), usdc_in, sol_out });
+            }
+            usdc_in /= ratio;
+            best = Some(BuyQuote { market: result.market.clone(), usdc_in, sol_out });
+        }
+        best
+    }
+
+    /// The market giving the most output for `amount` (UI units), or `None` if the swap fails on
+    /// every market.
+    pub fn route(&self, direction: SwapDirection, amount: f64) -> Result<Option<SwapResult>> {
+        Ok(self
+            .quote_all(direction, amount)?
+            .into_iter()
+            .filter(|r| r.out_amount.is_some())
+            .max_by(|a, b| {
+                a.out_amount.unwrap_or_default().total_cmp(&b.out_amount.unwrap_or_default())
+            }))
+    }
+}
+
+/// A single swap that can be replayed against modified copies of the snapshot. Every run starts
+/// from the same funded state, so runs don't affect each other.
+pub struct SwapHarness {
+    svm: LiteSVM,
+    user_keypair: Arc<Keypair>,
+    market: Pubkey,
+    direction: SwapDirection,
+    amount_in_atomic: u64,
+}
+
+impl SwapHarness {
+    /// Loads the snapshot in `data_dir` at `slot` with SolFi taken from `solfi` and funds a user to
+    /// swap `amount_in_atomic` on `market`.
+    pub fn new_in(
+        data_dir: &Path,
+        slot: Option<u64>,
+        solfi: Solfi,
+        market: Pubkey,
+        direction: SwapDirection,
+        amount_in_atomic: u64,
+    ) -> Result<Self> {
+        Simulator::load_with(data_dir, slot, solfi)?.harness(market, direction, amount_in_atomic)
+    }
+
+    pub fn market_data(&self) -> Result<Vec<u8>> {
+        let account =
+            self.svm.get_account(&self.market).ok_or(Error::MissingAccount(self.market))?;
+        Ok(account.data)
+    }
+
+    /// The instruction data of the unmodified swap.
+    pub fn swap_data(&self) -> Vec<u8> {
+        create_swap_ix(
+            self.direction,
+            &self.market,
+            &self.user_keypair.pubkey(),
+            &WSOL,
+            &USDC,
+            self.amount_in_atomic,
+        )
+        .data
+    }
+
+    /// Runs the swap, optionally with the market account data and/or the swap instruction data
+    /// replaced, and returns the output amount in UI units or the transaction error.
+    pub fn run(
+        &self,
+        market_data: Option<&[u8]>,
+        ix_data: Option<&[u8]>,
+    ) -> std::result::Result<f64, SimError> {
+        let mut svm = self.svm.clone();
+        if let Some(market_data) = market_data {
+            let mut market_account = svm.get_account(&self.market).unwrap_or_default();
+            market_account.data = market_data.to_vec();
+            svm.set_account(self.market, market_account)
+                .map_err(|e| SimError::Transaction { error: e.to_string() })?;
+        }
+
+        let user = self.user_keypair.pubkey();
+        let to_ata = output_ata(&user, self.direction);
+        let mut instructions =
+            swap_instructions(self.direction, &self.market, &user, self.amount_in_atomic)
+                .map_err(|e| SimError::Transaction { error: e.to_string() })?;
+        if let (Some(ix_data), Some(swap_ix)) = (ix_data, instructions.last_mut()) {
+            swap_ix.data = ix_data.to_vec();
+        }
+        let tx = Transaction::new_with_payer(&instructions, Some(&user));
+        let signed_tx =
+            Transaction::new(&[self.user_keypair.as_ref()], tx.message, svm.latest_blockhash());
+        svm.send_transaction(signed_tx).map_err(|e| SimError::from_failed_tx(&e, &instructions))?;
+
+        let out_amount_atomic = token_balance(&svm, &to_ata);
+        Ok(out_amount_atomic as f64 / 10f64.powi(decimals(self.direction).1))
+    }
+}
@@ -1,6 +1,6 @@
 use crate::constants::SOLFI_PROGRAM;
 use clap::ValueEnum;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use solana_pubkey::Pubkey;
 use solana_sdk::instruction::{AccountMeta, Instruction};
 use solana_sdk::sysvar;
@@ -8,14 +8,38 @@ use spl_associated_token_account::get_associated_token_address;
 use std::fmt;
 
 const DISCRIMINATOR: u8 = 7;
+const SWAP_DATA_LEN: usize = 18;
+const DIRECTION_OFFSET: usize = 17;
 
-#[derive(Clone, Copy, Default, Debug, PartialEq, Serialize, ValueEnum)]
+/// What each account passed by [`create_swap_ix`] is, in order.
+pub const SWAP_ACCOUNT_ROLES: [&str; 8] = [
+    "user",
+    "market",
+    "market vault A",
+    "market vault B",
+    "user token A",
+    "user token B",
+    "token program",
+    "instructions sysvar",
+];
+
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq, Hash, Serialize, Deserialize, ValueEnum)]
 pub enum SwapDirection {
     #[default]
     SolToUsdc,
     UsdcToSol,
 }
 
+impl SwapDirection {
+    /// The direction that swaps the output back.
+    pub fn opposite(self) -> Self {
+        match self {
+            SwapDirection::SolToUsdc => SwapDirection::UsdcToSol,
+            SwapDirection::UsdcToSol => SwapDirection::SolToUsdc,
+        }
+    }
+}
+
 impl fmt::Display for SwapDirection {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -25,15 +49,54 @@ impl fmt::Display for SwapDirection {
     }
 }
 
+/// The fields of a swap instruction's data.
+#[derive(Clone, Debug, Serialize)]
+pub struct SwapData {
+    pub amount_in: u64,
+    /// Bytes 9..17 as a little-endian u64, SolFi only. Undocumented; see the `ix-explore` command
+    /// for how the program reacts to them
+    pub unknown_9_17: Option<u64>,
+    pub direction_byte: u8,
+    pub direction: Option<SwapDirection>,
+    /// Anything after the direction byte
+    pub trailing: Vec<u8>,
+}
+
 fn create_instruction_data(direction: SwapDirection, amount_in: u64) -> Vec<u8> {
     let mut buffer = Vec::with_capacity(9);
     buffer.push(DISCRIMINATOR);
     buffer.extend_from_slice(&amount_in.to_le_bytes());
-    buffer.resize(18, 0);
-    buffer[17] = direction as u8;
+    buffer.resize(SWAP_DATA_LEN, 0);
+    buffer[DIRECTION_OFFSET] = direction as u8;
     buffer
 }
 
+/// Decodes SolFi instruction data, or returns `None` if it isn't a swap.
+pub fn decode_swap_data(data: &[u8]) -> Option<SwapData> {
+    if data.len() < SWAP_DATA_LEN || data[0] != DISCRIMINATOR {
+        return None;
+    }
+    let direction_byte = data[DIRECTION_OFFSET];
+    Some(SwapData {
+        amount_in: u64::from_le_bytes(data[1..9].try_into().ok()?),
+        unknown_9_17: Some(u64::from_le_bytes(data[9..17].try_into().ok()?)),
+        direction_byte,
+        direction: match direction_byte {
+            0 => Some(SwapDirection::SolToUsdc),
+            1 => Some(SwapDirection::UsdcToSol),
+            _ => None,
+        },
+        trailing: data[SWAP_DATA_LEN..].to_vec(),
+    })
+}
+
+/// Writes `min_amount_out` to bytes 9..17 of a swap instruction built by [`create_swap_ix`]. On
+/// the snapshots checked so far the swap fails with SolFi error 0x12 if it would pay out less,
+/// but the field is undocumented; `build-tx` re-checks that before relying on it.
+pub fn set_min_amount_out(swap_ix: &mut Instruction, min_amount_out: u64) {
+    swap_ix.data[9..DIRECTION_OFFSET].copy_from_slice(&min_amount_out.to_le_bytes());
+}
+
 pub fn create_swap_ix(
     direction: SwapDirection,
     market: &Pubkey,
@@ -0,0 +1,318 @@
+use litesvm::LiteSVM;
+use solana_account::{AccountSharedData, ReadableAccount, WritableAccount};
+use solana_bpf_loader_program::load_program_from_bytes;
+use solana_bpf_loader_program::syscalls::create_program_runtime_environment_v1;
+use solana_compute_budget::compute_budget::ComputeBudget;
+use solana_feature_set::FeatureSet;
+use solana_instructions_sysvar::{
+    self as instructions_sysvar, construct_instructions_data, store_current_index,
+};
+use solana_log_collector::LogCollector;
+use solana_message::SanitizedMessage;
+use solana_program_runtime::declare_process_instruction;
+use solana_program_runtime::invoke_context::{EnvironmentConfig, InvokeContext};
+use solana_program_runtime::loaded_programs::{
+    LoadProgramMetrics, ProgramCacheEntry, ProgramCacheForTxBatch,
+};
+use solana_program_runtime::solana_sbpf::ebpf;
+use solana_program_runtime::solana_sbpf::elf::Executable;
+use solana_program_runtime::sysvar_cache::SysvarCache;
+use solana_pubkey::Pubkey;
+use solana_sdk::instruction::InstructionError;
+use solana_sdk::sysvar::{self, clock, rent};
+use solana_sdk::{bpf_loader, native_loader};
+use solana_timings::ExecuteTimings;
+use solana_transaction_context::{IndexOfAccount, InstructionAccount, TransactionContext};
+use solfi_sim::error::{Error, Result};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// One SolFi invocation as seen from inside the VM.
+pub struct CapturedRun {
+    /// Instruction accounts in order, with where their data was mapped in the VM and its length
+    pub accounts: Vec<(Pubkey, u64, usize)>,
+    /// Register state (r0-r10, pc) before every executed instruction
+    pub trace: Vec<[u64; 12]>,
+}
+
+struct Tracer {
+    executable: Arc<Executable<InvokeContext<'static>>>,
+    runs: Vec<CapturedRun>,
+}
+
+thread_local! {
+    static TRACER: RefCell<Option<Tracer>> = const { RefCell::new(None) };
+}
+
+declare_process_instruction!(TracedProgram, 0, |invoke_context| {
+    let executable = TRACER
+        .with_borrow(|tracer| tracer.as_ref().map(|t| t.executable.clone()))
+        .ok_or(InstructionError::UnsupportedProgramId)?;
+
+    let result = solana_bpf_loader_program::execute(&executable, invoke_context);
+
+    // The syscall context (and with it the trace) only lives until this frame is popped.
+    if let Ok(syscall_context) = invoke_context.get_syscall_context() {
+        let transaction_context = &invoke_context.transaction_context;
+        let instruction_context = transaction_context.get_current_instruction_context()?;
+        let mut accounts = Vec::new();
+        for (i, meta) in syscall_context.accounts_metadata.iter().enumerate() {
+            let index =
+                instruction_context.get_index_of_instruction_account_in_transaction(i as _)?;
+            let key = *transaction_context.get_key_of_account_at_index(index)?;
+            accounts.push((key, meta.vm_data_addr, meta.original_data_len));
+        }
+        let run = CapturedRun { accounts, trace: syscall_context.trace_log.clone() };
+        TRACER.with_borrow_mut(|tracer| {
+            if let Some(tracer) = tracer {
+                tracer.runs.push(run);
+            }
+        });
+    }
+
+    result.map_err(|err| match err.downcast::<InstructionError>() {
+        Ok(err) => *err,
+        Err(_) => InstructionError::ProgramFailedToComplete,
+    })
+});
+
+/// The outcome of [`trace_instruction`].
+pub struct TracedInstruction {
+    /// The traced program's run, or `None` if it was never reached
+    pub run: Option<CapturedRun>,
+    pub result: std::result::Result<(), InstructionError>,
+    pub logs: Vec<String>,
+    /// Data of every account the instruction names, before it ran
+    pub pre_data: HashMap<Pubkey, Vec<u8>>,
+    /// Data of every account the instruction names, after it ran
+    pub post_data: HashMap<Pubkey, Vec<u8>>,
+}
+
+/// Runs instruction `index` of `message` against copies of its accounts in `svm`, with its program
+/// executing `program_bytes` in the interpreter with instruction tracing on. Programs it calls
+/// through CPI run from their snapshot accounts as usual.
+///
+/// LiteSVM has no supported way to register a builtin (0.6.1's `add_builtin` panics), so the
+/// instruction runs on the runtime's own invoke context instead of in a LiteSVM transaction.
+/// Signatures, fees and the instructions before `index` aren't executed; run those in `svm` first.
+/// The instructions sysvar still describes the whole message.
+pub fn trace_instruction(
+    svm: &LiteSVM,
+    message: &SanitizedMessage,
+    index: usize,
+    program_bytes: &[u8],
+) -> Result<TracedInstruction> {
+    let instructions = message.decompile_instructions();
+    let ix = instructions
+        .get(index)
+        .ok_or_else(|| Error::InvalidInput(format!("message has no instruction {index}")))?;
+    let feature_set = Arc::new(FeatureSet::all_enabled());
+    let compute_budget = ComputeBudget::default();
+    let runtime_error = |e: String| Error::Svm(format!("failed to create program runtime: {e}"));
+    // Same features LiteSVM::new() enables, plus the debugging features that turn on tracing.
+    let traced_environment =
+        create_program_runtime_environment_v1(&feature_set, &compute_budget, false, true)
+            .map_err(|e| runtime_error(e.to_string()))?;
+    let environment =
+        create_program_runtime_environment_v1(&feature_set, &compute_budget, false, false)
+            .map_err(|e| runtime_error(e.to_string()))?;
+    let executable = Executable::load(program_bytes, Arc::new(traced_environment))
+        .map_err(|e| Error::Svm(format!("failed to load program: {e}")))?;
+    TRACER.set(Some(Tracer { executable: Arc::new(executable), runs: vec![] }));
+
+    let mut keys: Vec<Pubkey> = vec![];
+    for key in ix.accounts.iter().map(|a| *a.pubkey).chain([clock::id(), rent::id()]) {
+        if !keys.contains(&key) {
+            keys.push(key);
+        }
+    }
+    let mut programs = ProgramCacheForTxBatch::default();
+    let loader = ProgramCacheEntry::new_builtin(0, 0, solana_bpf_loader_program::Entrypoint::vm);
+    programs.replenish(bpf_loader::id(), Arc::new(loader));
+    programs.replenish(
+        *ix.program_id,
+        Arc::new(ProgramCacheEntry::new_builtin(0, 0, TracedProgram::vm)),
+    );
+    let environment = Arc::new(environment);
+    let mut accounts = vec![];
+    for key in &keys {
+        if *key == instructions_sysvar::id() {
+            // Built per transaction by the bank, so it's never in the snapshot.
+            let mut data = construct_instructions_data(&instructions);
+            store_current_index(&mut data, index as u16);
+            let mut account = AccountSharedData::new(0, data.len(), &sysvar::id());
+            account.set_data_from_slice(&data);
+            accounts.push((*key, account));
+            continue;
+        }
+        let account = svm.get_account(key).unwrap_or_default();
+        if account.executable && account.owner == bpf_loader::id() {
+            let mut program = load_program_from_bytes(
+                None,
+                &mut LoadProgramMetrics::default(),
+                &account.data,
+                &account.owner,
+                account.data.len(),
+                0,
+                environment.clone(),
+                false,
+            )
+            .map_err(|e| Error::Svm(format!("failed to load program {key}: {e}")))?;
+            program.effective_slot = 0;
+            programs.replenish(*key, Arc::new(program));
+        }
+        accounts.push((*key, AccountSharedData::from(account)));
+    }
+    let pre_data: HashMap<Pubkey, Vec<u8>> =
+        accounts.iter().map(|(key, account)| (*key, account.data().to_vec())).collect();
+    let program_index = accounts.len() as IndexOfAccount;
+    let mut program_account = AccountSharedData::new(0, 0, &native_loader::id());
+    program_account.set_executable(true);
+    accounts.push((*ix.program_id, program_account));
+
+    let instruction_accounts: Vec<InstructionAccount> = ix
+        .accounts
+        .iter()
+        .enumerate()
+        .map(|(i, meta)| {
+            let index_in_transaction =
+                keys.iter().position(|k| k == meta.pubkey).unwrap() as IndexOfAccount;
+            let index_in_callee =
+                ix.accounts[..i].iter().position(|m| m.pubkey == meta.pubkey).unwrap_or(i);
+            InstructionAccount {
+                index_in_transaction,
+                index_in_caller: index_in_transaction,
+                index_in_callee: index_in_callee as IndexOfAccount,
+                is_signer: meta.is_signer,
+                is_writable: meta.is_writable,
+            }
+        })
+        .collect();
+
+    let mut sysvar_cache = SysvarCache::default();
+    sysvar_cache.fill_missing_entries(|key, set_sysvar| {
+        if let Some(account) = svm.get_account(key) {
+            set_sysvar(&account.data);
+        }
+    });
+    let mut transaction_context = TransactionContext::new(
+        accounts,
+        svm.get_sysvar(),
+        compute_budget.max_instruction_stack_depth,
+        compute_budget.max_instruction_trace_length,
+    );
+    let log_collector = LogCollector::new_ref();
+    let stake = |_: &Pubkey| 0;
+    let result = {
+        let environment_config = EnvironmentConfig::new(
+            svm.latest_blockhash(),
+            0,
+            0,
+            &stake,
+            feature_set,
+            &sysvar_cache,
+        );
+        let mut invoke_context = InvokeContext::new(
+            &mut transaction_context,
+            &mut programs,
+            environment_config,
+            Some(log_collector.clone()),
+            compute_budget,
+        );
+        invoke_context.process_instruction(
+            ix.data,
+            &instruction_accounts,
+            &[program_index],
+            &mut 0,
+            &mut ExecuteTimings::default(),
+        )
+    };
+
+    let mut post_data = HashMap::new();
+    for (i, key) in keys.iter().enumerate() {
+        let account = transaction_context
+            .get_account_at_index(i as IndexOfAccount)
+            .map_err(|e| Error::Svm(format!("lost account {key}: {e}")))?;
+        post_data.insert(*key, account.borrow().data().to_vec());
+    }
+    let run = TRACER.with_borrow_mut(|tracer| tracer.as_mut().and_then(|t| t.runs.pop()));
+    let logs = log_collector.borrow().get_recorded_content().to_vec();
+    Ok(TracedInstruction { run, result, logs, pre_data, post_data })
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+}
+
+/// A memory access made by a single VM instruction.
+#[derive(Clone, Copy, Debug)]
+pub struct MemoryAccess {
+    pub access: Access,
+    pub vm_addr: u64,
+    pub len: u64,
+}
+
+/// Decodes the loads and stores in `trace` against the traced program's text section.
+///
+/// Only the program's own load and store instructions show up here. Memory touched inside
+/// syscalls (`sol_memcpy_`, `sol_memcmp_`, `sol_memmove_`, CPI account copies, ...) never
+/// reaches the trace, so bytes the program only reads that way are missed.
+pub fn memory_accesses(trace: &[[u64; 12]]) -> Vec<MemoryAccess> {
+    TRACER.with_borrow(|tracer| {
+        let Some(tracer) = tracer else {
+            return vec![];
+        };
+        let executable = &tracer.executable;
+        let (_, text) = executable.get_text_bytes();
+        let moved_classes = executable.get_sbpf_version().move_memory_instruction_classes();
+
+        trace
+            .iter()
+            .filter_map(|regs| {
+                let pc = regs[11] as usize;
+                if (pc + 1) * ebpf::INSN_SIZE > text.len() {
+                    return None;
+                }
+                let insn = ebpf::get_insn_unchecked(text, pc);
+                let (access, len) = decode(insn.opc, moved_classes)?;
+                let reg = if access == Access::Read { insn.src } else { insn.dst } as usize;
+                let vm_addr = regs[reg].wrapping_add(insn.off as i64 as u64);
+                Some(MemoryAccess { access, vm_addr, len })
+            })
+            .collect()
+    })
+}
+
+/// Returns the access kind and width of memory opcodes, in either the legacy encoding or the one
+/// from SIMD-0173 that moved loads and stores into the ALU classes.
+fn decode(opc: u8, moved_classes: bool) -> Option<(Access, u64)> {
+    let decoded = if moved_classes {
+        match opc {
+            ebpf::LD_1B_REG => (Access::Read, 1),
+            ebpf::LD_2B_REG => (Access::Read, 2),
+            ebpf::LD_4B_REG => (Access::Read, 4),
+            ebpf::LD_8B_REG => (Access::Read, 8),
+            ebpf::ST_1B_IMM | ebpf::ST_1B_REG => (Access::Write, 1),
+            ebpf::ST_2B_IMM | ebpf::ST_2B_REG => (Access::Write, 2),
+            ebpf::ST_4B_IMM | ebpf::ST_4B_REG => (Access::Write, 4),
+            ebpf::ST_8B_IMM | ebpf::ST_8B_REG => (Access::Write, 8),
+            _ => return None,
+        }
+    } else {
+        match opc {
+            ebpf::LD_B_REG => (Access::Read, 1),
+            ebpf::LD_H_REG => (Access::Read, 2),
+            ebpf::LD_W_REG => (Access::Read, 4),
+            ebpf::LD_DW_REG => (Access::Read, 8),
+            ebpf::ST_B_IMM | ebpf::ST_B_REG => (Access::Write, 1),
+            ebpf::ST_H_IMM | ebpf::ST_H_REG => (Access::Write, 2),
+            ebpf::ST_W_IMM | ebpf::ST_W_REG => (Access::Write, 4),
+            ebpf::ST_DW_IMM | ebpf::ST_DW_REG => (Access::Write, 8),
+            _ => return None,
+        }
+    };
+    Some(decoded)
+}
@@ -1,12 +1,14 @@
+use crate::error::Result;
+use crate::utils::write_atomic;
 use serde::{Deserialize, Serialize};
 use solana_account::Account;
 use solana_pubkey::Pubkey;
 use std::fs;
 use std::fs::File;
-use std::io::{Read, Write};
+use std::io::Read;
 use std::path::{Path, PathBuf};
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct AccountWithAddress {
     pub address: Pubkey,
     pub account: Account,
@@ -17,21 +19,18 @@ impl AccountWithAddress {
         format!("account_{}.json", self.address)
     }
 
-    pub fn save_to_file(&self) -> eyre::Result<()> {
+    pub fn save_to_dir(&self, data_dir: &Path) -> Result<()> {
         let filename = self.get_filename();
         let serialized = serde_json::to_string(self)?;
-        let data_dir = Path::new("data");
         if !data_dir.exists() {
-            fs::create_dir(data_dir)?;
+            fs::create_dir_all(data_dir)?;
         }
-        let file_path = data_dir.join(filename);
-        let mut file = File::create(file_path)?;
-        file.write_all(serialized.as_bytes())?;
+        write_atomic(&data_dir.join(filename), serialized.as_bytes())?;
 
         Ok(())
     }
 
-    pub fn read_account(path: PathBuf) -> eyre::Result<AccountWithAddress> {
+    pub fn read_account(path: PathBuf) -> Result<AccountWithAddress> {
         let mut file = File::open(&path)?;
         let mut contents = String::new();
         file.read_to_string(&mut contents)?;
@@ -40,8 +39,7 @@ impl AccountWithAddress {
         Ok(account_with_address)
     }
 
-    pub fn read_all() -> eyre::Result<Vec<Self>> {
-        let data_dir = Path::new("data");
+    pub fn read_all_from(data_dir: &Path) -> Result<Vec<Self>> {
         if !data_dir.exists() {
             return Ok(vec![]);
         }
@@ -1,26 +1,57 @@
+use crate::error::Result;
+use crate::utils::write_atomic;
 use serde::{Deserialize, Serialize};
 use std::fmt::{Display, Formatter};
 use std::fs;
-use std::path::PathBuf;
+use std::path::Path;
 
-const FILE_PATH: &str = "data/metadata.json";
+const FILE_NAME: &str = "metadata.json";
+
+/// Where a snapshot's block time came from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BlockTimeSource {
+    /// `getBlockTime` for the fetched slot
+    Rpc,
+    /// The fetching machine's clock, when the RPC had no block time for the slot yet or wasn't
+    /// asked. Only approximately the chain's time
+    LocalClock,
+}
 
 #[derive(Serialize, Deserialize)]
 pub struct FetchMetadata {
     slot: Option<u64>,
 
+    /// Unix timestamp (seconds) of the fetched slot
+    #[serde(default)]
+    block_time: Option<i64>,
+
+    /// Unknown for snapshots fetched before sources were recorded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    block_time_source: Option<BlockTimeSource>,
+
     // backward-compatability
     slot_lower: u64,
     slot_upper: u64,
 }
 
 impl FetchMetadata {
-    pub fn new(slot: u64) -> Self {
-        Self { slot: Some(slot), slot_lower: slot, slot_upper: slot }
+    /// Metadata for a snapshot at `slot` with the chain's `block_time`, if known.
+    pub fn new(slot: u64, block_time: Option<i64>) -> Self {
+        let block_time_source = block_time.map(|_| BlockTimeSource::Rpc);
+        Self { slot: Some(slot), block_time, block_time_source, slot_lower: slot, slot_upper: slot }
+    }
+
+    /// Metadata for a snapshot at `slot` timed by the local clock instead of the chain.
+    pub fn with_local_clock(slot: u64, block_time: Option<i64>) -> Self {
+        Self {
+            block_time_source: block_time.map(|_| BlockTimeSource::LocalClock),
+            ..Self::new(slot, block_time)
+        }
     }
 
-    pub fn read() -> Option<Self> {
-        let path = PathBuf::from(FILE_PATH);
+    pub fn read_from(data_dir: &Path) -> Option<Self> {
+        let path = data_dir.join(FILE_NAME);
         if !path.exists() {
             return None;
         }
@@ -33,12 +64,17 @@ impl FetchMetadata {
         self.slot.unwrap_or(self.slot_lower)
     }
 
-    pub fn save_to_file(&self) -> eyre::Result<()> {
-        let path = PathBuf::from(FILE_PATH);
-        if let Some(parent) = path.parent() {
-            fs::create_dir_all(parent)?;
-        }
-        fs::write(&path, serde_json::to_string(self)?)?;
+    pub fn block_time(&self) -> Option<i64> {
+        self.block_time
+    }
+
+    pub fn block_time_source(&self) -> Option<BlockTimeSource> {
+        self.block_time_source
+    }
+
+    pub fn save_to_dir(&self, data_dir: &Path) -> Result<()> {
+        fs::create_dir_all(data_dir)?;
+        write_atomic(&data_dir.join(FILE_NAME), serde_json::to_string(self)?.as_bytes())?;
         Ok(())
     }
 }
@@ -46,9 +82,16 @@ impl FetchMetadata {
 impl Display for FetchMetadata {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         if self.slot_lower == self.slot_upper {
-            write!(f, "fetched at slot {}", self.slot_lower)
+            write!(f, "fetched at slot {}", self.slot_lower)?;
         } else {
-            write!(f, "fetched between slots {} and {}", self.slot_lower, self.slot_upper)
+            write!(f, "fetched between slots {} and {}", self.slot_lower, self.slot_upper)?;
+        }
+        if let Some(block_time) = self.block_time {
+            write!(f, ", block time {block_time}")?;
+            if self.block_time_source == Some(BlockTimeSource::LocalClock) {
+                write!(f, " (local clock)")?;
+            }
         }
+        Ok(())
     }
 }
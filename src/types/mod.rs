@@ -1,5 +1,7 @@
 mod account;
 mod metadata;
+mod sim_error;
 
 pub use account::AccountWithAddress;
-pub use metadata::FetchMetadata;
+pub use metadata::{BlockTimeSource, FetchMetadata};
+pub use sim_error::{ErrorProgram, SimError};
@@ -0,0 +1,143 @@
+use crate::constants::SOLFI_PROGRAM;
+use litesvm::types::FailedTransactionMetadata;
+use num_traits::FromPrimitive;
+use serde::{Deserialize, Serialize};
+use solana_pubkey::Pubkey;
+use solana_sdk::instruction::{Instruction, InstructionError};
+use solana_sdk::transaction::TransactionError;
+use spl_associated_token_account::error::AssociatedTokenAccountError;
+use spl_token::error::TokenError;
+use std::fmt;
+use std::str::FromStr;
+
+/// Program that raised an error.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ErrorProgram {
+    Solfi,
+    SplToken,
+    AssociatedToken,
+    System,
+    Other,
+}
+
+impl ErrorProgram {
+    fn from_id(program_id: &Pubkey) -> Self {
+        if *program_id == SOLFI_PROGRAM {
+            ErrorProgram::Solfi
+        } else if *program_id == spl_token::id() {
+            ErrorProgram::SplToken
+        } else if *program_id == spl_associated_token_account::id() {
+            ErrorProgram::AssociatedToken
+        } else if *program_id == solana_system_interface::program::id() {
+            ErrorProgram::System
+        } else {
+            ErrorProgram::Other
+        }
+    }
+
+    /// Known name for a custom error code raised by this program. SolFi's source isn't public,
+    /// so its codes stay unnamed.
+    fn error_name(&self, code: u32) -> Option<String> {
+        match self {
+            ErrorProgram::SplToken => TokenError::from_u32(code).map(|e| format!("{e:?}")),
+            ErrorProgram::AssociatedToken
+                if code == AssociatedTokenAccountError::InvalidOwner as u32 =>
+            {
+                Some(format!("{:?}", AssociatedTokenAccountError::InvalidOwner))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ErrorProgram {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorProgram::Solfi => write!(f, "solfi"),
+            ErrorProgram::SplToken => write!(f, "spl-token"),
+            ErrorProgram::AssociatedToken => write!(f, "associated-token"),
+            ErrorProgram::System => write!(f, "system"),
+            ErrorProgram::Other => write!(f, "other"),
+        }
+    }
+}
+
+/// Why a simulated transaction failed, in a form that can be grouped and counted.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SimError {
+    /// A program returned a custom error code
+    Custom { instruction: u8, program: ErrorProgram, code: u32, name: Option<String> },
+    /// An instruction failed with one of the runtime's own errors
+    Instruction { instruction: u8, program: ErrorProgram, error: String },
+    /// The transaction failed outside of any instruction, or couldn't be built at all
+    Transaction { error: String },
+}
+
+impl SimError {
+    /// Classifies a failed transaction made of `instructions`. The program is taken from the
+    /// logs when they show which program failed, so errors raised inside a CPI (e.g. a token
+    /// transfer made by SolFi) are attributed to the inner program.
+    pub fn from_failed_tx(
+        failed: &FailedTransactionMetadata,
+        instructions: &[Instruction],
+    ) -> Self {
+        let TransactionError::InstructionError(instruction, err) = &failed.err else {
+            return SimError::Transaction { error: failed.err.to_string() };
+        };
+        let program = failed
+            .meta
+            .logs
+            .iter()
+            .find_map(|line| {
+                let rest = line.strip_prefix("Program ")?;
+                let (id, _) = rest.split_once(" failed: ")?;
+                Pubkey::from_str(id).ok()
+            })
+            .or_else(|| instructions.get(*instruction as usize).map(|ix| ix.program_id))
+            .map_or(ErrorProgram::Other, |id| ErrorProgram::from_id(&id));
+
+        match err {
+            InstructionError::Custom(code) => SimError::Custom {
+                instruction: *instruction,
+                program,
+                code: *code,
+                name: program.error_name(*code),
+            },
+            err => {
+                SimError::Instruction { instruction: *instruction, program, error: err.to_string() }
+            }
+        }
+    }
+
+    /// Short identifier without the instruction index, for grouping and counting.
+    pub fn label(&self) -> String {
+        match self {
+            SimError::Custom { program, code, name: Some(name), .. } => {
+                format!("{program}:{code:#x}:{name}")
+            }
+            SimError::Custom { program, code, name: None, .. } => format!("{program}:{code:#x}"),
+            SimError::Instruction { program, error, .. } => format!("{program}:{error}"),
+            SimError::Transaction { error } => format!("transaction:{error}"),
+        }
+    }
+}
+
+impl fmt::Display for SimError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SimError::Custom { instruction, program, code, name } => {
+                write!(f, "instruction {instruction} ({program}): custom error {code:#x}")?;
+                if let Some(name) = name {
+                    write!(f, " {name}")?;
+                }
+                Ok(())
+            }
+            SimError::Instruction { instruction, program, error } => {
+                write!(f, "instruction {instruction} ({program}): {error}")
+            }
+            SimError::Transaction { error } => write!(f, "{error}"),
+        }
+    }
+}
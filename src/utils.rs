@@ -2,6 +2,8 @@ use litesvm::LiteSVM;
 use solana_pubkey::Pubkey;
 use solana_sdk::program_pack::Pack;
 use spl_token::state::Account as AccountState;
+use std::fs;
+use std::path::Path;
 
 pub fn token_balance(svm: &LiteSVM, pubkey: &Pubkey) -> u64 {
     let account = svm.get_account(pubkey).unwrap_or_default();
@@ -9,7 +11,17 @@ pub fn token_balance(svm: &LiteSVM, pubkey: &Pubkey) -> u64 {
     state.amount
 }
 
-pub fn u64_at_offset(data: &[u8], offset: usize) -> eyre::Result<u64> {
-    let bytes = &data[offset..offset + 8];
-    Ok(u64::from_le_bytes(bytes.try_into()?))
+/// Writes `contents` to `path` through a temporary file renamed over it, so readers see either
+/// the old file or the new one, never a partial write.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    fs::write(&tmp, contents)?;
+    fs::rename(&tmp, path)
+}
+
+/// The little-endian `u64` at `offset`, or `None` if `data` is too short.
+pub fn u64_at_offset(data: &[u8], offset: usize) -> Option<u64> {
+    let bytes = data.get(offset..offset.checked_add(8)?)?;
+    Some(u64::from_le_bytes(bytes.try_into().ok()?))
 }
@@ -0,0 +1,360 @@
+use crate::constants::{SOLFI_MARKETS, SOLFI_PROGRAM, SOLFI_PROGRAM_PATH, USDC, WSOL};
+use crate::error::{Error, Result};
+use crate::swap::{SwapData, SwapDirection, create_swap_ix, decode_swap_data};
+use crate::utils::u64_at_offset;
+use serde::{Deserialize, Deserializer};
+use solana_pubkey::Pubkey;
+use solana_sdk::instruction::{AccountMeta, Instruction};
+use spl_associated_token_account::get_associated_token_address;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// An on-chain program that swaps WSOL/USDC and can be simulated next to SolFi. The user's token
+/// accounts are always their WSOL and USDC ATAs, created and funded by the simulator.
+pub trait Venue: Send + Sync {
+    /// Short name used in output
+    fn name(&self) -> &str;
+
+    fn program_id(&self) -> Pubkey;
+
+    /// The program binary
+    fn program_path(&self) -> &Path;
+
+    /// Directory with this venue's account snapshot (`account_*.json` files), or `None` if its
+    /// accounts are part of the main snapshot
+    fn snapshot_dir(&self) -> Option<&Path>;
+
+    /// Markets that can be quoted
+    fn markets(&self) -> Vec<Pubkey>;
+
+    /// Every account a snapshot needs to simulate swaps on the markets
+    fn accounts(&self) -> Vec<Pubkey>;
+
+    /// The swap instruction selling `amount_in` atomic units for `user`.
+    fn swap_ix(
+        &self,
+        market: &Pubkey,
+        user: &Pubkey,
+        direction: SwapDirection,
+        amount_in: u64,
+    ) -> Result<Instruction>;
+
+    /// Decodes swap instruction data, or returns `None` if it isn't a swap.
+    fn decode_swap(&self, data: &[u8]) -> Option<SwapData>;
+}
+
+/// SolFi's WSOL/USDC markets. The default has the markets and program from
+/// [`constants`](crate::constants).
+#[derive(Clone, Debug)]
+pub struct Solfi {
+    markets: Vec<Pubkey>,
+    program_path: PathBuf,
+}
+
+impl Solfi {
+    pub fn new(markets: Vec<Pubkey>, program_path: PathBuf) -> Self {
+        Self { markets, program_path }
+    }
+}
+
+impl Default for Solfi {
+    fn default() -> Self {
+        Self::new(SOLFI_MARKETS.to_vec(), SOLFI_PROGRAM_PATH.into())
+    }
+}
+
+impl Venue for Solfi {
+    fn name(&self) -> &str {
+        "solfi"
+    }
+
+    fn program_id(&self) -> Pubkey {
+        SOLFI_PROGRAM
+    }
+
+    fn program_path(&self) -> &Path {
+        &self.program_path
+    }
+
+    fn snapshot_dir(&self) -> Option<&Path> {
+        None
+    }
+
+    fn markets(&self) -> Vec<Pubkey> {
+        self.markets.clone()
+    }
+
+    fn accounts(&self) -> Vec<Pubkey> {
+        [WSOL, USDC]
+            .into_iter()
+            .chain(self.markets.iter().flat_map(|market| {
+                [
+                    *market,
+                    get_associated_token_address(market, &WSOL),
+                    get_associated_token_address(market, &USDC),
+                ]
+            }))
+            .collect()
+    }
+
+    fn swap_ix(
+        &self,
+        market: &Pubkey,
+        user: &Pubkey,
+        direction: SwapDirection,
+        amount_in: u64,
+    ) -> Result<Instruction> {
+        Ok(create_swap_ix(direction, market, user, &WSOL, &USDC, amount_in))
+    }
+
+    fn decode_swap(&self, data: &[u8]) -> Option<SwapData> {
+        decode_swap_data(data)
+    }
+}
+
+/// An account of a configured swap instruction: one of the user's accounts, the market, or a
+/// fixed address.
+#[derive(Clone, Copy, Debug)]
+pub enum AccountKey {
+    User,
+    UserWsol,
+    UserUsdc,
+    /// The user's account for the mint being sold
+    UserInput,
+    /// The user's account for the mint being bought
+    UserOutput,
+    Market,
+    Address(Pubkey),
+}
+
+impl FromStr for AccountKey {
+    type Err = String;
+
+    fn from_str(key: &str) -> std::result::Result<Self, Self::Err> {
+        Ok(match key {
+            "user" => AccountKey::User,
+            "user_wsol" => AccountKey::UserWsol,
+            "user_usdc" => AccountKey::UserUsdc,
+            "user_input" => AccountKey::UserInput,
+            "user_output" => AccountKey::UserOutput,
+            "market" => AccountKey::Market,
+            address => AccountKey::Address(
+                Pubkey::from_str(address).map_err(|e| format!("invalid account {address}: {e}"))?,
+            ),
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for AccountKey {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        String::deserialize(deserializer)?.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+fn deserialize_pubkey<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Pubkey, D::Error> {
+    Pubkey::from_str(&String::deserialize(deserializer)?).map_err(serde::de::Error::custom)
+}
+
+fn deserialize_hex<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Vec<u8>, D::Error> {
+    let hex = String::deserialize(deserializer)?;
+    if hex.len() % 2 != 0 {
+        return Err(serde::de::Error::custom(format!("odd-length hex {hex}")));
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).map_err(serde::de::Error::custom))
+        .collect()
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct AccountConfig {
+    pub key: AccountKey,
+    #[serde(default)]
+    pub signer: bool,
+    #[serde(default)]
+    pub writable: bool,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct MarketConfig {
+    #[serde(deserialize_with = "deserialize_pubkey")]
+    pub address: Pubkey,
+    /// The swap instruction's accounts, in order
+    pub accounts: Vec<AccountConfig>,
+}
+
+fn default_direction_values() -> [u8; 2] {
+    [0, 1]
+}
+
+/// Where the fields of the swap instruction data are. Amounts are little-endian `u64`s. Bytes
+/// not covered here are left zero, so a minimum output field, if the program has one, asks for
+/// no minimum, the same as the SolFi swaps the simulator builds.
+#[derive(Clone, Debug, Deserialize)]
+pub struct DataLayout {
+    /// Leading bytes (the discriminator), as hex
+    #[serde(deserialize_with = "deserialize_hex")]
+    pub prefix: Vec<u8>,
+    pub len: usize,
+    pub amount_in_offset: usize,
+    /// Byte holding the direction, if the program takes one rather than inferring it from the
+    /// account order
+    pub direction_offset: Option<usize>,
+    /// Direction byte values for sol-to-usdc and usdc-to-sol
+    #[serde(default = "default_direction_values")]
+    pub direction_values: [u8; 2],
+}
+
+/// A venue described by a config file rather than code.
+#[derive(Clone, Debug, Deserialize)]
+pub struct ConfiguredVenue {
+    pub name: String,
+    #[serde(deserialize_with = "deserialize_pubkey")]
+    pub program_id: Pubkey,
+    pub program_path: PathBuf,
+    pub snapshot_dir: Option<PathBuf>,
+    pub markets: Vec<MarketConfig>,
+    pub data: DataLayout,
+}
+
+impl ConfiguredVenue {
+    fn validate(&self) -> Result<()> {
+        let invalid =
+            |reason: String| Err(Error::InvalidInput(format!("venue {}: {reason}", self.name)));
+        let layout = &self.data;
+        if layout.amount_in_offset.checked_add(8).is_none_or(|end| end > layout.len) {
+            let offset = layout.amount_in_offset;
+            return invalid(format!("amount at {offset} doesn't fit in {} bytes", layout.len));
+        }
+        if layout.prefix.len() > layout.len {
+            return invalid("prefix is longer than the data".into());
+        }
+        if layout.direction_offset.is_some_and(|o| o >= layout.len) {
+            return invalid("direction offset is past the end of the data".into());
+        }
+        if self.markets.is_empty() {
+            return invalid("no markets".into());
+        }
+        if !self.markets.iter().all(|m| m.accounts.iter().any(|a| a.signer)) {
+            return invalid("every market needs a signer account".into());
+        }
+        Ok(())
+    }
+
+    fn market(&self, address: &Pubkey) -> Option<&MarketConfig> {
+        self.markets.iter().find(|m| m.address == *address)
+    }
+}
+
+impl Venue for ConfiguredVenue {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn program_id(&self) -> Pubkey {
+        self.program_id
+    }
+
+    fn program_path(&self) -> &Path {
+        &self.program_path
+    }
+
+    fn snapshot_dir(&self) -> Option<&Path> {
+        self.snapshot_dir.as_deref()
+    }
+
+    fn markets(&self) -> Vec<Pubkey> {
+        self.markets.iter().map(|m| m.address).collect()
+    }
+
+    fn accounts(&self) -> Vec<Pubkey> {
+        let mut accounts = vec![];
+        for market in &self.markets {
+            accounts.push(market.address);
+            for account in &market.accounts {
+                if let AccountKey::Address(address) = account.key
+                    && !accounts.contains(&address)
+                {
+                    accounts.push(address);
+                }
+            }
+        }
+        accounts
+    }
+
+    fn swap_ix(
+        &self,
+        market: &Pubkey,
+        user: &Pubkey,
+        direction: SwapDirection,
+        amount_in: u64,
+    ) -> Result<Instruction> {
+        let config = self.market(market).ok_or_else(|| {
+            Error::InvalidInput(format!("venue {}: unknown market {market}", self.name))
+        })?;
+        let (user_wsol, user_usdc) =
+            (get_associated_token_address(user, &WSOL), get_associated_token_address(user, &USDC));
+        let (user_input, user_output) = match direction {
+            SwapDirection::SolToUsdc => (user_wsol, user_usdc),
+            SwapDirection::UsdcToSol => (user_usdc, user_wsol),
+        };
+        let accounts = config
+            .accounts
+            .iter()
+            .map(|account| {
+                let pubkey = match account.key {
+                    AccountKey::User => *user,
+                    AccountKey::UserWsol => user_wsol,
+                    AccountKey::UserUsdc => user_usdc,
+                    AccountKey::UserInput => user_input,
+                    AccountKey::UserOutput => user_output,
+                    AccountKey::Market => *market,
+                    AccountKey::Address(address) => address,
+                };
+                AccountMeta { pubkey, is_signer: account.signer, is_writable: account.writable }
+            })
+            .collect();
+
+        let layout = &self.data;
+        let mut data = vec![0; layout.len];
+        data[..layout.prefix.len()].copy_from_slice(&layout.prefix);
+        data[layout.amount_in_offset..layout.amount_in_offset + 8]
+            .copy_from_slice(&amount_in.to_le_bytes());
+        if let Some(offset) = layout.direction_offset {
+            data[offset] = layout.direction_values[direction as usize];
+        }
+        Ok(Instruction { program_id: self.program_id, accounts, data })
+    }
+
+    fn decode_swap(&self, data: &[u8]) -> Option<SwapData> {
+        let layout = &self.data;
+        if data.len() < layout.len || !data.starts_with(&layout.prefix) {
+            return None;
+        }
+        let direction_byte = layout.direction_offset.map_or(0, |offset| data[offset]);
+        Some(SwapData {
+            amount_in: u64_at_offset(data, layout.amount_in_offset)?,
+            unknown_9_17: None,
+            direction_byte,
+            direction: layout.direction_offset.and_then(|_| {
+                let position = layout.direction_values.iter().position(|v| *v == direction_byte)?;
+                Some([SwapDirection::SolToUsdc, SwapDirection::UsdcToSol][position])
+            }),
+            trailing: data[layout.len..].to_vec(),
+        })
+    }
+}
+
+/// Reads and validates a JSON list of [`ConfiguredVenue`]s.
+pub fn load_venues(path: &Path) -> Result<Vec<ConfiguredVenue>> {
+    let venues: Vec<ConfiguredVenue> = serde_json::from_str(&fs::read_to_string(path)?)?;
+    for venue in &venues {
+        venue.validate()?;
+    }
+    Ok(venues)
+}
@@ -0,0 +1,163 @@
+// Each test crate uses a different part of this module
+#![allow(dead_code)]
+
+use base64::Engine;
+use base64::prelude::BASE64_STANDARD;
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{Value, json};
+use solana_account::Account;
+use solana_pubkey::Pubkey;
+use solfi_sim::mock_rpc::{MockRpc, MockRpcServer, MockScript};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc::{UnboundedSender, unbounded_channel};
+use tokio_tungstenite::tungstenite::Message;
+
+/// A node at `slot` with no accounts, so only the context slot tells which endpoint answered.
+pub async fn mock_rpc(slot: u64) -> MockRpcServer {
+    MockRpc::new(vec![], slot, MockScript::default()).start().await.unwrap()
+}
+
+enum PubSubCommand {
+    Notify { address: Pubkey, slot: u64, account: Account },
+    Close,
+}
+
+/// A stand-in for the Solana PubSub API that confirms `accountSubscribe` requests and sends the
+/// notifications a test scripts to the latest connection.
+pub struct MockPubSub {
+    pub url: String,
+    connections: Arc<AtomicUsize>,
+    subscriptions: Arc<AtomicUsize>,
+    current: Arc<Mutex<Option<UnboundedSender<PubSubCommand>>>>,
+}
+
+impl MockPubSub {
+    pub async fn start() -> Self {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let connections = Arc::new(AtomicUsize::new(0));
+        let subscriptions = Arc::new(AtomicUsize::new(0));
+        let current = Arc::new(Mutex::new(None));
+        let (conns, subs, cur) = (connections.clone(), subscriptions.clone(), current.clone());
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let Ok(ws) = tokio_tungstenite::accept_async(stream).await else { continue };
+                let (tx, mut rx) = unbounded_channel();
+                *cur.lock().unwrap() = Some(tx);
+                subs.store(0, Ordering::SeqCst);
+                conns.fetch_add(1, Ordering::SeqCst);
+                let subs = subs.clone();
+                tokio::spawn(async move {
+                    let (mut sink, mut stream) = ws.split();
+                    let mut by_address = HashMap::new();
+                    loop {
+                        tokio::select! {
+                            Some(Ok(Message::Text(text))) = stream.next() => {
+                                let req: Value = serde_json::from_str(&text).unwrap();
+                                let address: Pubkey = req["params"][0].as_str().unwrap().parse().unwrap();
+                                let subscription = 1000 + req["id"].as_u64().unwrap();
+                                by_address.insert(address, subscription);
+                                let resp = json!({ "jsonrpc": "2.0", "result": subscription, "id": req["id"] });
+                                sink.send(Message::text(resp.to_string())).await.unwrap();
+                                subs.fetch_add(1, Ordering::SeqCst);
+                            }
+                            Some(command) = rx.recv() => match command {
+                                PubSubCommand::Notify { address, slot, account } => {
+                                    let notification = json!({
+                                        "jsonrpc": "2.0",
+                                        "method": "accountNotification",
+                                        "params": {
+                                            "result": {
+                                                "context": { "slot": slot },
+                                                "value": {
+                                                    "lamports": account.lamports,
+                                                    "data": [BASE64_STANDARD.encode(&account.data), "base64"],
+                                                    "owner": account.owner.to_string(),
+                                                    "executable": account.executable,
+                                                    "rentEpoch": account.rent_epoch,
+                                                    "space": account.data.len(),
+                                                },
+                                            },
+                                            "subscription": by_address[&address],
+                                        },
+                                    });
+                                    sink.send(Message::text(notification.to_string())).await.unwrap();
+                                }
+                                PubSubCommand::Close => {
+                                    let _ = sink.close().await;
+                                    break;
+                                }
+                            },
+                            else => break,
+                        }
+                    }
+                });
+            }
+        });
+        Self { url, connections, subscriptions, current }
+    }
+
+    fn send(&self, command: PubSubCommand) {
+        let current = self.current.lock().unwrap();
+        current.as_ref().expect("no connection").send(command).unwrap();
+    }
+
+    pub fn notify(&self, address: Pubkey, slot: u64, lamports: u64) {
+        let account = Account { lamports, ..Account::default() };
+        self.send(PubSubCommand::Notify { address, slot, account });
+    }
+
+    /// Closes the latest connection.
+    pub fn close(&self) {
+        self.send(PubSubCommand::Close);
+    }
+
+    pub fn connections(&self) -> usize {
+        self.connections.load(Ordering::SeqCst)
+    }
+
+    /// Waits until the latest connection has `count` subscriptions.
+    pub async fn wait_for_subscriptions(&self, count: usize) {
+        while self.subscriptions.load(Ordering::SeqCst) < count {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    }
+}
+
+/// An empty directory for one test's files.
+pub fn temp_dir(name: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("solfi-sim-{name}-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Sends a raw HTTP request to `addr` and returns the status and JSON body of the response.
+async fn http_json(addr: &str, method: &str, path: &str, body: &str) -> (u16, Value) {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+    let request = format!(
+        "{method} {path} HTTP/1.1\r\nHost: {addr}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    let status = head.split_whitespace().nth(1).unwrap().parse().unwrap();
+    (status, serde_json::from_str(body).unwrap())
+}
+
+/// Posts a JSON-RPC request to the service at `addr` and returns the response body.
+pub async fn post_json(addr: &str, body: &Value) -> Value {
+    http_json(addr, "POST", "/", &body.to_string()).await.1
+}
+
+/// Gets `path` from the service at `addr`, returning the status and response body.
+pub async fn get_json(addr: &str, path: &str) -> (u16, Value) {
+    http_json(addr, "GET", path, "").await
+}
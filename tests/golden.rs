@@ -0,0 +1,120 @@
+//! Quotes on the bundled snapshot in `data/`, compared exactly with the files in
+//! `tests/golden/`. They pin the output formats too (CSV columns, error messages). After a change
+//! that's meant to move either, regenerate them with `UPDATE_GOLDENS=1 cargo test --test golden`
+//! and review the diff.
+
+use serde_json::{Value, json};
+use solfi_sim::{Simulator, SwapDirection};
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+const BIN: &str = env!("CARGO_BIN_EXE_solfi-sim");
+const GOLDEN_DIR: &str = "tests/golden";
+
+/// CSV column of `simulate --wide` holding compute units. They depend on the randomly generated
+/// wallet each run swaps from, so they're left out.
+const COMPUTE_UNITS_COLUMN: usize = 9;
+
+fn assert_golden(name: &str, actual: &str) {
+    let path = Path::new(GOLDEN_DIR).join(name);
+    if std::env::var_os("UPDATE_GOLDENS").is_some() {
+        fs::write(&path, actual).unwrap();
+        return;
+    }
+    let expected = fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("{}: {e}; create it with UPDATE_GOLDENS=1", path.display()));
+    assert!(
+        expected == actual,
+        "{name} changed (UPDATE_GOLDENS=1 regenerates it)\n--- expected\n{expected}\n--- actual\n{actual}"
+    );
+}
+
+fn solfi_sim(args: &[&str]) -> String {
+    let output = Command::new(BIN)
+        .args(["--config", &format!("{GOLDEN_DIR}/solfi-sim.toml"), "--data-dir", "data"])
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+fn without_compute_units(csv: &str) -> String {
+    csv.lines()
+        .map(|line| {
+            let mut fields: Vec<&str> = line.split(',').collect();
+            fields.remove(COMPUTE_UNITS_COLUMN);
+            fields.join(",") + "\n"
+        })
+        .collect()
+}
+
+#[test]
+fn simulate_sol_to_usdc() {
+    let csv = solfi_sim(&["simulate", "-a", "10", "--wide"]);
+    assert_golden("simulate_sol_to_usdc.csv", &without_compute_units(&csv));
+}
+
+#[test]
+fn simulate_usdc_to_sol() {
+    let csv = solfi_sim(&["simulate", "-a", "1000", "--direction", "usdc-to-sol", "--wide"]);
+    assert_golden("simulate_usdc_to_sol.csv", &without_compute_units(&csv));
+}
+
+#[test]
+fn spreads() {
+    assert_golden("spreads.txt", &solfi_sim(&["spreads", "100"]));
+}
+
+/// Every market over sizes from dust to beyond what the pools hold, in both directions.
+#[test]
+fn size_sweep() {
+    let simulator = Simulator::load(Path::new("data")).unwrap();
+    let mut quotes = vec![];
+    for direction in [SwapDirection::SolToUsdc, SwapDirection::UsdcToSol] {
+        let price = match direction {
+            SwapDirection::SolToUsdc => 1.0,
+            SwapDirection::UsdcToSol => 200.0,
+        };
+        for sol in [0.001, 0.1, 1.0, 10.0, 100.0, 1_000.0, 10_000.0, 100_000.0] {
+            for result in simulator.quote_all(direction, sol * price).unwrap() {
+                quotes.push(json!({
+                    "direction": direction.to_string(),
+                    "market": result.market,
+                    "in_amount": result.in_amount,
+                    "out_amount": result.out_amount,
+                    "error": result.error.map(|e| e.to_string()),
+                    "net_price": result.net_price,
+                }));
+            }
+        }
+    }
+    let json = serde_json::to_string_pretty(&Value::Array(quotes)).unwrap();
+    assert_golden("size_sweep.json", &(json + "\n"));
+}
+
+/// The service's buy side: the USDC that buys each SOL size, searched from 1% over the price
+/// selling 1 SOL gets and from the 150 USDC `get_prices` falls back to without sells. The first
+/// lands within tolerance right away, the second has to rescale. Past what the pools hold, the
+/// search finds nothing or keeps the last quote that filled.
+#[test]
+fn search_buy() {
+    let simulator = Simulator::load(Path::new("data")).unwrap();
+    let sell = simulator.route(SwapDirection::SolToUsdc, 1.0).unwrap().unwrap();
+    let mut quotes = vec![];
+    for initial_price in [sell.out_amount.unwrap() * 1.01, 150.0] {
+        for sol in [0.1, 1.0, 10.0, 100.0, 1_000.0, 10_000.0] {
+            let mut tries = 0;
+            let quote = simulator.search_buy(sol, initial_price, |_| tries += 1);
+            quotes.push(json!({
+                "initial_price": initial_price,
+                "target_sol": sol,
+                "tries": tries,
+                "quote": quote,
+            }));
+        }
+    }
+    let json = serde_json::to_string_pretty(&Value::Array(quotes)).unwrap();
+    assert_golden("search_buy.json", &(json + "\n"));
+}
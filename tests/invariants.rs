@@ -0,0 +1,147 @@
+use proptest::prelude::*;
+use solfi_sim::constants::SOLFI_MARKETS;
+use solfi_sim::invariants::{
+    Invariant, InvariantOptions, Violation, check_curve, check_invariants,
+};
+use solfi_sim::{Result, Simulator, SwapDirection};
+use std::path::Path;
+
+const SIZES: [f64; 6] = [0.01, 0.1, 1.0, 10.0, 100.0, 1_000.0];
+
+/// A constant-product pool holding `sol` and `usdc` that charges `fee_bps`, rounding outputs down
+/// to whole units like the program does.
+fn constant_product(
+    sol: f64,
+    usdc: f64,
+    fee_bps: f64,
+) -> impl FnMut(SwapDirection, f64) -> Result<Option<f64>> {
+    move |direction, amount_in| {
+        let (reserve_in, reserve_out, unit) = match direction {
+            SwapDirection::SolToUsdc => (sol, usdc, 1e-6),
+            SwapDirection::UsdcToSol => (usdc, sol, 1e-9),
+        };
+        let amount_in = amount_in * (1.0 - fee_bps / 10_000.0);
+        let out = reserve_out * amount_in / (reserve_in + amount_in);
+        Ok(Some((out / unit).floor() * unit))
+    }
+}
+
+fn check(quote: impl FnMut(SwapDirection, f64) -> Result<Option<f64>>) -> Vec<Violation> {
+    check_curve("market", &SIZES, InvariantOptions::default(), quote).unwrap()
+}
+
+fn broken(violations: &[Violation], invariant: Invariant) -> Vec<(SwapDirection, f64)> {
+    violations.iter().filter(|v| v.invariant == invariant).map(|v| (v.direction, v.size)).collect()
+}
+
+#[test]
+fn constant_product_holds() {
+    assert!(check(constant_product(10_000.0, 1_880_000.0, 5.0)).is_empty());
+}
+
+#[test]
+fn reports_shrinking_output() {
+    let mut pool = constant_product(10_000.0, 1_880_000.0, 5.0);
+    let violations = check(move |direction, amount| {
+        let out = pool(direction, amount)?;
+        Ok(if direction == SwapDirection::SolToUsdc && amount == 100.0 {
+            out.map(|o| o / 20.0)
+        } else {
+            out
+        })
+    });
+    assert_eq!(
+        broken(&violations, Invariant::MonotonicOutput),
+        [(SwapDirection::SolToUsdc, 100.0)]
+    );
+}
+
+#[test]
+fn reports_improving_price() {
+    // Pays a bonus on the part of a sale above 10 SOL
+    let violations = check(|direction, amount| {
+        Ok(Some(match direction {
+            SwapDirection::SolToUsdc => 180.0 * amount + 10.0 * (amount - 10.0).max(0.0),
+            SwapDirection::UsdcToSol => amount / 200.0,
+        }))
+    });
+    assert_eq!(broken(&violations, Invariant::WorseningPrice), [(SwapDirection::SolToUsdc, 100.0)]);
+}
+
+#[test]
+fn reports_round_trip_profit_and_crossed_directions() {
+    // Buys SOL cheaper than it sells it
+    let violations = check(|direction, amount| {
+        Ok(Some(match direction {
+            SwapDirection::SolToUsdc => amount * 190.0,
+            SwapDirection::UsdcToSol => amount / 185.0,
+        }))
+    });
+    assert_eq!(broken(&violations, Invariant::NoRoundTripProfit).len(), 2 * SIZES.len());
+    assert_eq!(broken(&violations, Invariant::DirectionsAgree).len(), SIZES.len());
+}
+
+#[test]
+fn reports_disagreeing_directions() {
+    let wide = check(|direction, amount| {
+        Ok(Some(match direction {
+            SwapDirection::SolToUsdc => amount * 180.0,
+            SwapDirection::UsdcToSol => amount / 200.0,
+        }))
+    });
+    assert_eq!(broken(&wide, Invariant::DirectionsAgree), [(SwapDirection::UsdcToSol, 1.8)]);
+
+    let one_sided = check(|direction, amount| {
+        Ok((direction == SwapDirection::SolToUsdc).then_some(amount * 180.0))
+    });
+    assert_eq!(broken(&one_sided, Invariant::DirectionsAgree), [(SwapDirection::UsdcToSol, 1.8)]);
+}
+
+#[test]
+fn bundled_snapshot_holds() {
+    let simulator = Simulator::load(Path::new("data")).unwrap();
+    let violations = check_invariants(&simulator, &SIZES, InvariantOptions::default()).unwrap();
+    assert!(violations.is_empty(), "{violations:#?}");
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn constant_product_holds_for_any_pool(
+        sol in 100.0..1e6f64,
+        price in 10.0..1_000.0f64,
+        // A round trip pays the fee twice, so fees approaching half of
+        // `InvariantOptions::max_spread_bps` (100) trip `DirectionsAgree`; keep well below that.
+        fee_bps in 0.0..40.0f64,
+    ) {
+        let violations = check(constant_product(sol, sol * price, fee_bps));
+        prop_assert!(violations.is_empty(), "{:#?}", violations);
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(16))]
+
+    #[test]
+    fn bundled_markets_hold_at_any_size(
+        market in 0..SOLFI_MARKETS.len(),
+        small in 0.001..500.0f64,
+        factor in 1.0..4.0f64,
+    ) {
+        let simulator = Simulator::load(Path::new("data")).unwrap();
+        let market = SOLFI_MARKETS[market];
+        let quote = |direction, amount| simulator.quote(&market, direction, amount).ok();
+
+        let (Some(less), Some(more)) = (
+            quote(SwapDirection::SolToUsdc, small),
+            quote(SwapDirection::SolToUsdc, small * factor),
+        ) else {
+            return Ok(());
+        };
+        prop_assert!(less <= more, "{small} SOL pays {less} USDC, {} SOL {more}", small * factor);
+        if let Some(back) = quote(SwapDirection::UsdcToSol, less) {
+            prop_assert!(back <= small, "{small} SOL sells for {less} USDC, which buys {back}");
+        }
+    }
+}
@@ -0,0 +1,250 @@
+mod common;
+
+use common::{get_json, post_json, temp_dir};
+use serde_json::json;
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use solfi_sim::constants::{SOLFI_MARKETS, SOLFI_PROGRAM};
+use solfi_sim::mock_rpc::{MockFailure, MockPhase, MockRpc, MockRpcServer, MockScript};
+use solfi_sim::types::{AccountWithAddress, FetchMetadata};
+use std::fs;
+use std::path::Path;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::process::{Child, Command};
+use tokio::time::{sleep, timeout};
+
+const BIN: &str = env!("CARGO_BIN_EXE_solfi-sim");
+const SNAPSHOT_SLOT: u64 = 356315274;
+
+fn stored_len(address: &solana_pubkey::Pubkey) -> usize {
+    let path = format!("data/account_{address}.json");
+    AccountWithAddress::read_account(path.into()).unwrap().account.data.len()
+}
+
+async fn snapshot_rpc(script: MockScript) -> MockRpcServer {
+    MockRpc::from_dir(Path::new("data"), script).unwrap().start().await.unwrap()
+}
+
+#[tokio::test]
+async fn serves_snapshot_accounts() {
+    let rpc = snapshot_rpc(MockScript::default()).await;
+    let client = RpcClient::new(rpc.url.clone());
+
+    assert_eq!(client.get_slot().await.unwrap(), SNAPSHOT_SLOT);
+    let market = SOLFI_MARKETS[0];
+    let stored = AccountWithAddress::read_account(format!("data/account_{market}.json").into());
+    assert_eq!(client.get_account(&market).await.unwrap(), stored.unwrap().account);
+
+    let mut markets: Vec<_> = client
+        .get_program_accounts(&SOLFI_PROGRAM)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|(address, _)| address)
+        .collect();
+    markets.sort();
+    let mut expected = SOLFI_MARKETS.to_vec();
+    expected.sort();
+    assert_eq!(markets, expected);
+
+    let request = |filters| {
+        json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "getProgramAccounts",
+            "params": [SOLFI_PROGRAM.to_string(), { "encoding": "base64", "filters": filters }],
+        })
+    };
+    let addr = rpc.url.trim_start_matches("http://");
+    let size = stored_len(&SOLFI_MARKETS[0]);
+    let sized = post_json(addr, &request(json!([{ "dataSize": size }]))).await;
+    assert_eq!(sized["result"].as_array().unwrap().len(), SOLFI_MARKETS.len());
+    let none = post_json(addr, &request(json!([{ "dataSize": size + 1 }]))).await;
+    assert!(none["result"].as_array().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn follows_script() {
+    let script = MockScript {
+        start_slot: Some(10),
+        phases: vec![
+            MockPhase { requests: Some(2), advance: 1, ..Default::default() },
+            MockPhase { requests: Some(1), fail: Some(MockFailure::Rpc), ..Default::default() },
+            MockPhase { requests: Some(1), fail: Some(MockFailure::Http), ..Default::default() },
+            MockPhase { requests: Some(1), delay_ms: 300, advance: 5, ..Default::default() },
+        ],
+    };
+    let rpc = snapshot_rpc(script).await;
+    let client = RpcClient::new(rpc.url.clone());
+
+    assert_eq!(client.get_slot().await.unwrap(), 11);
+    assert_eq!(client.get_slot().await.unwrap(), 12);
+    let err = client.get_slot().await.unwrap_err();
+    assert!(err.to_string().contains("Node is behind"), "{err}");
+    let err = client.get_slot().await.unwrap_err();
+    assert!(err.to_string().contains("503"), "{err}");
+    let start = std::time::Instant::now();
+    assert_eq!(client.get_slot().await.unwrap(), 17);
+    assert!(start.elapsed() >= Duration::from_millis(300));
+    // Steady after the script ends
+    assert_eq!(client.get_slot().await.unwrap(), 17);
+    assert_eq!(rpc.requests(), 6);
+}
+
+#[tokio::test]
+async fn fetch_accounts_copies_snapshot() {
+    let rpc = snapshot_rpc(MockScript::default()).await;
+    let dir = temp_dir("fetch");
+
+    let status = Command::new(BIN)
+        .args(["--rpc-url", &rpc.url, "--data-dir", dir.to_str().unwrap(), "fetch-accounts"])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .await
+        .unwrap();
+    assert!(status.success());
+
+    for account in AccountWithAddress::read_all_from(Path::new("data")).unwrap() {
+        let name = format!("account_{}.json", account.address);
+        assert_eq!(
+            fs::read(dir.join(&name)).unwrap(),
+            fs::read(Path::new("data").join(&name)).unwrap(),
+            "{name}"
+        );
+    }
+    assert_eq!(FetchMetadata::read_from(&dir).unwrap().slot(), SNAPSHOT_SLOT);
+}
+
+/// Runs the service against `rpc`, fetching into `dir`, until the returned child is dropped.
+async fn start_service(rpc: &MockRpcServer, dir: &Path) -> (Child, String) {
+    let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let config = dir.join("solfi-sim.toml");
+    fs::write(
+        &config,
+        format!(
+            "data_dir = {:?}\nrpc_urls = [{:?}]\n\n[service]\nfetch_interval_ms = 100\nladder = [1]\n",
+            dir.join("data"),
+            rpc.url
+        ),
+    )
+    .unwrap();
+
+    let service = Command::new(BIN)
+        .args(["--config", config.to_str().unwrap(), "service", "--port", &port.to_string()])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()
+        .unwrap();
+
+    let addr = format!("127.0.0.1:{port}");
+    timeout(Duration::from_secs(30), async {
+        while tokio::net::TcpStream::connect(&addr).await.is_err() {
+            sleep(Duration::from_millis(50)).await;
+        }
+    })
+    .await
+    .expect("service didn't start");
+    (service, addr)
+}
+
+#[tokio::test]
+async fn service_quotes_fetched_snapshot() {
+    let script = MockScript {
+        phases: vec![MockPhase { advance: 1, ..Default::default() }],
+        ..Default::default()
+    };
+    let rpc = snapshot_rpc(script).await;
+    let dir = temp_dir("service");
+    let (_service, addr) = start_service(&rpc, &dir).await;
+
+    let response =
+        post_json(&addr, &json!({ "jsonrpc": "2.0", "id": 1, "method": "get_prices" })).await;
+    let result = &response["result"];
+    assert_eq!(result["sell_sol"].as_array().unwrap().len(), 1, "{response}");
+    let sell = result["sell_sol"][0]["price_usdc"].as_f64().unwrap();
+    let buy = result["buy_sol"][0]["price_usdc"].as_f64().unwrap();
+    assert!(150.0 < sell && sell < buy && buy < 250.0, "{response}");
+
+    // The fetch loop keeps following the node's slot
+    let first = FetchMetadata::read_from(&dir.join("data")).unwrap().slot();
+    assert!(first > SNAPSHOT_SLOT);
+    timeout(Duration::from_secs(10), async {
+        while FetchMetadata::read_from(&dir.join("data")).is_none_or(|m| m.slot() <= first) {
+            sleep(Duration::from_millis(50)).await;
+        }
+    })
+    .await
+    .expect("fetch loop stalled");
+}
+
+#[tokio::test]
+async fn service_unavailable_before_first_snapshot() {
+    let script = MockScript {
+        phases: vec![MockPhase { fail: Some(MockFailure::Http), ..Default::default() }],
+        ..Default::default()
+    };
+    let rpc = snapshot_rpc(script).await;
+    let dir = temp_dir("unavailable");
+    let (_service, addr) = start_service(&rpc, &dir).await;
+
+    let (status, body) = get_json(&addr, "/snapshot").await;
+    assert_eq!(status, 503, "{body}");
+    let response =
+        post_json(&addr, &json!({ "jsonrpc": "2.0", "id": 1, "method": "get_snapshot" })).await;
+    assert_eq!(response["error"]["code"], -32000, "{response}");
+}
+
+#[tokio::test]
+async fn service_serves_rest() {
+    let rpc = snapshot_rpc(MockScript::default()).await;
+    let dir = temp_dir("rest");
+    let (_service, addr) = start_service(&rpc, &dir).await;
+    let market = SOLFI_MARKETS[1].to_string();
+
+    let (status, snapshot) = get_json(&addr, "/snapshot").await;
+    assert_eq!(status, 200);
+    assert_eq!(snapshot["slot"], SNAPSHOT_SLOT);
+
+    let (_, markets) = get_json(&addr, "/markets").await;
+    assert_eq!(markets.as_array().unwrap().len(), SOLFI_MARKETS.len());
+
+    // REST and JSON-RPC answer from the same snapshot with the same data
+    let (status, state) = get_json(&addr, &format!("/markets/{market}/state")).await;
+    assert_eq!(status, 200);
+    let rpc_state = post_json(
+        &addr,
+        &json!({ "jsonrpc": "2.0", "id": 1, "method": "get_market_state", "params": { "market": market } }),
+    )
+    .await;
+    assert_eq!(state, rpc_state["result"]);
+    assert_eq!(
+        state["slots_until_cutoff"],
+        state["cutoff_slot"].as_i64().unwrap() - SNAPSHOT_SLOT as i64
+    );
+
+    let (status, quotes) = get_json(&addr, &format!("/quote?amount=10&market={market}")).await;
+    assert_eq!(status, 200);
+    let out = quotes[0]["out_amount"].as_f64().unwrap();
+    assert!(1500.0 < out && out < 2500.0, "{quotes}");
+    let (_, buys) = get_json(&addr, "/quote?amount=1000&direction=usdc-to-sol").await;
+    assert_eq!(buys.as_array().unwrap().len(), SOLFI_MARKETS.len());
+
+    let (_, spreads) = get_json(&addr, "/spreads?usdc=100").await;
+    assert!(spreads[0]["spread_bps"].as_f64().unwrap() >= 0.0, "{spreads}");
+
+    assert_eq!(get_json(&addr, "/quote?amount=0").await.0, 400);
+    assert_eq!(get_json(&addr, "/quote?amount=1&direction=up").await.0, 400);
+    let unknown = SOLFI_PROGRAM.to_string();
+    assert_eq!(get_json(&addr, &format!("/markets/{unknown}/state")).await.0, 404);
+
+    let (_, metrics) = get_json(&addr, "/metrics").await;
+    assert_eq!(metrics["requests"]["get_market_state"], 3);
+
+    let (status, openapi) = get_json(&addr, "/openapi.json").await;
+    assert_eq!(status, 200);
+    for path in ["/quote", "/markets", "/markets/{id}/state", "/snapshot", "/spreads"] {
+        assert!(openapi["paths"][path]["get"].is_object(), "{path} missing from {openapi}");
+    }
+}
@@ -0,0 +1,109 @@
+mod common;
+
+use common::{MockPubSub, mock_rpc};
+use solana_account::Account;
+use solana_pubkey::Pubkey;
+use solfi_sim::pubsub::{
+    Snapshot, SnapshotBuilder, StreamOptions, stream_snapshots, subscribe_accounts,
+};
+use solfi_sim::rpc::RpcPool;
+use solfi_sim::types::AccountWithAddress;
+use std::time::Duration;
+use tokio::sync::mpsc::unbounded_channel;
+use tokio::time::timeout;
+
+fn account(lamports: u64) -> Account {
+    Account { lamports, ..Account::default() }
+}
+
+fn lamports(snapshot: &Snapshot, address: &Pubkey) -> Option<u64> {
+    snapshot.accounts.iter().find(|a| a.address == *address).map(|a| a.account.lamports)
+}
+
+#[test]
+fn builder_completes_slot_when_next_one_starts() {
+    let (a, b) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let seed = Snapshot {
+        slot: 10,
+        accounts: vec![AccountWithAddress { address: a, account: account(1) }],
+    };
+    let mut builder = SnapshotBuilder::new(&seed);
+
+    assert!(builder.apply(11, a, account(2)).is_none());
+    assert!(builder.apply(11, b, account(3)).is_none());
+    let snapshot = builder.apply(12, a, account(4)).unwrap();
+    assert_eq!(snapshot.slot, 11);
+    assert_eq!((lamports(&snapshot, &a), lamports(&snapshot, &b)), (Some(2), Some(3)));
+
+    // Older than the pending slot
+    assert!(builder.apply(11, b, account(9)).is_none());
+    let snapshot = builder.flush().unwrap();
+    assert_eq!(snapshot.slot, 12);
+    assert_eq!((lamports(&snapshot, &a), lamports(&snapshot, &b)), (Some(4), Some(3)));
+    assert!(builder.flush().is_none());
+}
+
+#[tokio::test]
+async fn subscription_builds_snapshots_per_slot() {
+    let pubsub = MockPubSub::start().await;
+    let (a, b) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let seed = Snapshot { slot: 100, accounts: vec![] };
+
+    let url = pubsub.url.clone();
+    let subscriber = tokio::spawn(async move {
+        let mut snapshots = vec![];
+        subscribe_accounts(&url, &[a, b], &seed, |s| snapshots.push(s)).await.map(|_| snapshots)
+    });
+    pubsub.wait_for_subscriptions(2).await;
+    pubsub.notify(a, 101, 2);
+    pubsub.notify(b, 101, 3);
+    pubsub.notify(a, 102, 4);
+    pubsub.notify(b, 99, 9);
+    pubsub.close();
+
+    let snapshots = subscriber.await.unwrap().unwrap();
+    let slots: Vec<u64> = snapshots.iter().map(|s| s.slot).collect();
+    assert_eq!(slots, [101, 102]);
+    assert_eq!((lamports(&snapshots[0], &a), lamports(&snapshots[0], &b)), (Some(2), Some(3)));
+    assert_eq!((lamports(&snapshots[1], &a), lamports(&snapshots[1], &b)), (Some(4), Some(3)));
+}
+
+#[tokio::test]
+async fn falls_back_to_polling_and_resubscribes() {
+    let (rpc, pubsub) = (mock_rpc(200).await, MockPubSub::start().await);
+    let pool = RpcPool::new(std::slice::from_ref(&rpc.url)).unwrap();
+    let market = Pubkey::new_unique();
+    let options = StreamOptions {
+        poll_interval: Duration::from_millis(50),
+        resubscribe_after: Duration::from_millis(300),
+    };
+
+    let (tx, mut rx) = unbounded_channel();
+    let ws_url = pubsub.url.clone();
+    let streamer = tokio::spawn(async move {
+        stream_snapshots(&pool, &ws_url, &[market], options, |s| tx.send(s).unwrap()).await
+    });
+    let mut next_slot =
+        async || timeout(Duration::from_secs(5), rx.recv()).await.unwrap().unwrap().slot;
+
+    // Seeded from a poll, then pushed
+    assert_eq!(next_slot().await, 200);
+    pubsub.wait_for_subscriptions(1).await;
+    pubsub.notify(market, 201, 5);
+    assert_eq!(next_slot().await, 201);
+
+    // Polls while the WebSocket is down
+    rpc.set_slot(205);
+    pubsub.close();
+    assert_eq!(next_slot().await, 205);
+
+    // and subscribes again
+    timeout(Duration::from_secs(5), async {
+        while pubsub.connections() < 2 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .unwrap();
+    streamer.abort();
+}
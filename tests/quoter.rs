@@ -0,0 +1,110 @@
+use solfi_sim::SwapDirection;
+use solfi_sim::quoter::{ApproxQuoter, CurveModel, Fit, FitKind, FittedCurve};
+
+/// Average price `c0 + c1 x + c2 x^2` in `x = ln(amount) - 1`, sampled so that the fit maps
+/// `ln(amount)` in `[0, 2]` straight onto `x` in `[-1, 1]`.
+fn quadratic_samples(coeffs: [f64; 3], count: usize) -> Vec<(f64, f64)> {
+    (0..count)
+        .map(|i| {
+            let log_amount = 2.0 * i as f64 / (count - 1) as f64;
+            let x = log_amount - 1.0;
+            let amount = log_amount.exp();
+            (amount, amount * (coeffs[0] + coeffs[1] * x + coeffs[2] * x * x))
+        })
+        .collect()
+}
+
+fn coeffs(fit: &Fit) -> &[f64] {
+    match fit {
+        Fit::Polynomial { coeffs, .. } => coeffs,
+        Fit::PiecewiseLinear { .. } => panic!("expected a polynomial fit"),
+    }
+}
+
+#[test]
+fn polynomial_fit_recovers_coefficients() {
+    let expected = [2.0, 0.5, -0.25];
+    let samples = quadratic_samples(expected, 9);
+
+    let fit = Fit::from_samples(FitKind::Polynomial, 2, &samples).unwrap();
+    let Fit::Polynomial { center, scale, .. } = &fit else { unreachable!() };
+    assert!((center - 1.0).abs() < 1e-12 && (scale - 1.0).abs() < 1e-12, "{fit:?}");
+    for (got, want) in coeffs(&fit).iter().zip(expected) {
+        assert!((got - want).abs() < 1e-9, "{fit:?}");
+    }
+
+    // Extra terms come out zero rather than bending the curve
+    let fit = Fit::from_samples(FitKind::Polynomial, 4, &samples).unwrap();
+    assert_eq!(coeffs(&fit).len(), 5);
+    for (got, want) in coeffs(&fit).iter().zip(expected.into_iter().chain([0.0, 0.0])) {
+        assert!((got - want).abs() < 1e-7, "{fit:?}");
+    }
+}
+
+#[test]
+fn polynomial_degree_is_capped_by_samples() {
+    let fit = Fit::from_samples(FitKind::Polynomial, 5, &quadratic_samples([2.0, 0.5, -0.25], 3))
+        .unwrap();
+    assert_eq!(coeffs(&fit).len(), 3);
+}
+
+#[test]
+fn degenerate_samples_are_refused() {
+    // Every sample at the same size leaves nothing to fit a slope to
+    let same_size = vec![(10.0, 1_880.0); 5];
+    assert!(Fit::from_samples(FitKind::Polynomial, 2, &same_size).is_err());
+
+    // Failed swaps (zero output) don't count as samples
+    let one_success = vec![(1.0, 188.0), (10.0, 0.0), (100.0, 0.0)];
+    assert!(Fit::from_samples(FitKind::Polynomial, 1, &one_success).is_err());
+    assert!(Fit::from_samples(FitKind::PiecewiseLinear, 1, &one_success).is_err());
+}
+
+/// A curve paying a flat `price` per unit between 1 and 100 units in.
+fn flat_curve(market: &str, direction: SwapDirection, price: f64) -> FittedCurve {
+    FittedCurve {
+        market: market.to_string(),
+        direction,
+        min_in: 1.0,
+        max_in: 100.0,
+        fit: Fit::PiecewiseLinear { log_sizes: vec![0.0, 100f64.ln()], prices: vec![price, price] },
+        max_error_bps: None,
+        mean_error_bps: None,
+    }
+}
+
+fn quoter() -> ApproxQuoter {
+    ApproxQuoter::new(CurveModel {
+        slot: 1_000,
+        kind: FitKind::PiecewiseLinear,
+        curves: vec![
+            flat_curve("a", SwapDirection::SolToUsdc, 187.0),
+            flat_curve("b", SwapDirection::SolToUsdc, 188.0),
+            flat_curve("a", SwapDirection::UsdcToSol, 1.0 / 188.5),
+            flat_curve("b", SwapDirection::UsdcToSol, 1.0 / 189.0),
+        ],
+    })
+}
+
+#[test]
+fn best_quote_picks_the_highest_output() {
+    let quoter = quoter();
+
+    let (market, out) = quoter.best_quote(SwapDirection::SolToUsdc, 10.0).unwrap();
+    assert_eq!(market, "b");
+    assert!((out - 1_880.0).abs() < 1e-9);
+    let (market, _) = quoter.best_quote(SwapDirection::UsdcToSol, 10.0).unwrap();
+    assert_eq!(market, "a");
+
+    // Beyond every sampled range there's nothing to quote
+    assert_eq!(quoter.best_quote(SwapDirection::SolToUsdc, 1_000.0), None);
+    assert_eq!(quoter.quote("c", SwapDirection::SolToUsdc, 10.0), None);
+}
+
+#[test]
+fn model_is_stale_once_the_slot_moves() {
+    let quoter = quoter();
+    assert!(!quoter.is_stale(1_000));
+    assert!(quoter.is_stale(1_001));
+    assert!(quoter.is_stale(999));
+}
@@ -0,0 +1,101 @@
+mod common;
+
+use common::mock_rpc;
+use solana_pubkey::Pubkey;
+use solfi_sim::mock_rpc::MockRpcServer;
+use solfi_sim::rpc::RpcPool;
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+fn pool(endpoints: &[&MockRpcServer]) -> RpcPool {
+    let urls: Vec<String> = endpoints.iter().map(|e| e.url.clone()).collect();
+    RpcPool::with_timeout(&urls, TIMEOUT).unwrap()
+}
+
+async fn fetched_slot(pool: &RpcPool) -> solfi_sim::Result<u64> {
+    Ok(pool.get_multiple_accounts(&[Pubkey::new_unique()]).await?.0)
+}
+
+#[tokio::test]
+async fn uses_configured_order_before_health_check() {
+    let (a, b) = (mock_rpc(100).await, mock_rpc(200).await);
+    assert_eq!(fetched_slot(&pool(&[&a, &b])).await.unwrap(), 100);
+}
+
+#[tokio::test]
+async fn prefers_highest_slot() {
+    let (lagging, current) = (mock_rpc(100).await, mock_rpc(200).await);
+    let pool = pool(&[&lagging, &current]);
+    pool.health_check().await;
+    assert_eq!(fetched_slot(&pool).await.unwrap(), 200);
+    assert_eq!(pool.health()[0].0, current.url);
+}
+
+#[tokio::test]
+async fn prefers_lowest_latency_among_caught_up() {
+    let (slow, fast) = (mock_rpc(201).await, mock_rpc(200).await);
+    slow.set_delay(Duration::from_millis(200));
+    let pool = pool(&[&slow, &fast]);
+    pool.health_check().await;
+    assert_eq!(fetched_slot(&pool).await.unwrap(), 200);
+}
+
+#[tokio::test]
+async fn fails_over_on_outage() {
+    let (primary, backup) = (mock_rpc(300).await, mock_rpc(299).await);
+    let pool = pool(&[&primary, &backup]);
+    assert_eq!(fetched_slot(&pool).await.unwrap(), 300);
+
+    primary.set_down(true);
+    backup.set_slot(301);
+    assert_eq!(fetched_slot(&pool).await.unwrap(), 301);
+    let health = pool.health();
+    assert_eq!(health[0].0, backup.url);
+    assert_eq!(health[1].1.failures, 1);
+
+    primary.set_down(false);
+    primary.set_slot(310);
+    pool.health_check().await;
+    assert_eq!(fetched_slot(&pool).await.unwrap(), 310);
+}
+
+#[tokio::test]
+async fn rejects_slot_going_backwards() {
+    let (a, b) = (mock_rpc(500).await, mock_rpc(400).await);
+    let pool = pool(&[&a, &b]);
+    assert_eq!(fetched_slot(&pool).await.unwrap(), 500);
+
+    a.set_down(true);
+    let err = fetched_slot(&pool).await.unwrap_err();
+    assert!(err.to_string().contains("slot 400 is behind slot 500"), "{err}");
+    assert_eq!(pool.last_slot(), Some(500));
+
+    b.set_slot(501);
+    assert_eq!(fetched_slot(&pool).await.unwrap(), 501);
+}
+
+#[tokio::test]
+async fn times_out_stalled_endpoint() {
+    let (stalled, backup) = (mock_rpc(100).await, mock_rpc(100).await);
+    stalled.set_delay(TIMEOUT * 2);
+    assert_eq!(fetched_slot(&pool(&[&stalled, &backup])).await.unwrap(), 100);
+}
+
+#[tokio::test]
+async fn errors_when_every_endpoint_is_down() {
+    let (a, b) = (mock_rpc(1).await, mock_rpc(1).await);
+    a.set_down(true);
+    b.set_down(true);
+    assert!(matches!(fetched_slot(&pool(&[&a, &b])).await, Err(solfi_sim::Error::Rpc(_))));
+}
+
+#[tokio::test]
+async fn blockhash_fails_over_on_outage() {
+    let (primary, backup) = (mock_rpc(300).await, mock_rpc(301).await);
+    primary.set_down(true);
+    let pool = pool(&[&primary, &backup]);
+    let blockhash = pool.get_latest_blockhash().await.unwrap();
+    assert_eq!(blockhash, solana_sdk::hash::hash(&301u64.to_le_bytes()));
+    assert_eq!(pool.health()[1].1.failures, 1);
+}
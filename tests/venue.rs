@@ -0,0 +1,60 @@
+use serde_json::{Value, json};
+use solana_pubkey::Pubkey;
+use solfi_sim::venue::{ConfiguredVenue, load_venues};
+use solfi_sim::{SwapDirection, Venue};
+use std::fs;
+
+const MARKET: &str = "5guD4Uz462GT4Y4gEuqyGsHZ59JGxFN4a3rF6KWguMcJ";
+
+/// A venue taking `[0x09, amount, direction]` with the user and market as its accounts.
+fn venue_json(amount_in_offset: u64) -> Value {
+    json!({
+        "name": "other-amm",
+        "program_id": "11111111111111111111111111111111",
+        "program_path": "other_amm.so",
+        "markets": [{
+            "address": MARKET,
+            "accounts": [
+                { "key": "user", "signer": true, "writable": true },
+                { "key": "market", "writable": true },
+            ],
+        }],
+        "data": { "prefix": "09", "len": 10, "amount_in_offset": amount_in_offset, "direction_offset": 9 },
+    })
+}
+
+fn load(venue: Value, name: &str) -> solfi_sim::Result<Vec<ConfiguredVenue>> {
+    let path = std::env::temp_dir().join(format!("solfi-sim-{name}-{}.json", std::process::id()));
+    fs::write(&path, json!([venue]).to_string()).unwrap();
+    let venues = load_venues(&path);
+    let _ = fs::remove_file(&path);
+    venues
+}
+
+#[test]
+fn configured_swap_decodes() {
+    let venue = load(venue_json(1), "decode").unwrap().remove(0);
+    let user = Pubkey::new_unique();
+    let ix =
+        venue.swap_ix(&MARKET.parse().unwrap(), &user, SwapDirection::UsdcToSol, 1234).unwrap();
+    assert_eq!(ix.accounts[0].pubkey, user);
+
+    let swap = venue.decode_swap(&ix.data).unwrap();
+    assert_eq!(swap.amount_in, 1234);
+    assert_eq!(swap.direction, Some(SwapDirection::UsdcToSol));
+    assert_eq!(swap.unknown_9_17, None);
+    assert!(venue.decode_swap(&[0x08; 10]).is_none());
+}
+
+#[test]
+fn configured_swap_rejects_unknown_market() {
+    let venue = load(venue_json(1), "unknown").unwrap().remove(0);
+    let market = Pubkey::new_unique();
+    let result = venue.swap_ix(&market, &Pubkey::new_unique(), SwapDirection::SolToUsdc, 1);
+    assert!(result.is_err());
+}
+
+#[test]
+fn configured_amount_offset_cannot_overflow() {
+    assert!(load(venue_json(u64::MAX), "overflow").is_err());
+}